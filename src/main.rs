@@ -1,11 +1,22 @@
+use std::process::ExitCode;
+
 use clap::Parser;
-use rcli::{CmdExecutor, Opts};
+use rcli::{CmdExecutor, Exit, Opts};
 
 // rcli csv -i input.csv -o output.json --header -d ','
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> ExitCode {
     tracing_subscriber::fmt::init();
     let opts = Opts::parse();
-    opts.cmd.execute().await
+    match opts.cmd.execute().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => match e.downcast_ref::<Exit>() {
+            Some(Exit(code)) => ExitCode::from(*code),
+            None => {
+                eprintln!("Error: {:?}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
 }
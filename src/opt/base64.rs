@@ -1,6 +1,11 @@
+use std::io::{IsTerminal, Write};
 use std::{fmt::Display, str::FromStr};
 
-use crate::{CmdExecutor, process_base64_decode, process_base64_encode, verify_file};
+use crate::{
+    CmdExecutor, CompressFormat, get_writer, parse_compress_format, process_base64_decode,
+    process_base64_encode, process_datauri_decode, process_datauri_encode, verify_file,
+    write_decoded,
+};
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
@@ -11,28 +16,44 @@ pub enum Base64SubCommand {
     Encode(Base64EncodeOpts),
     #[command(name = "decode", about = "Decode a base64 string")]
     Decode(Base64DecodeOpts),
+    #[command(name = "datauri", about = "Encode a file as a data: URI")]
+    DataUri(Base64DataUriOpts),
+    #[command(name = "datauri-decode", about = "Extract the payload of a data: URI")]
+    DataUriDecode(Base64DataUriDecodeOpts),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Base64Format {
     UrlSafe,
+    UrlSafePad,
     Standard,
+    StandardNoPad,
 }
 
 #[derive(Debug, Parser)]
 pub struct Base64EncodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
+    #[arg(short, long)]
+    pub output: Option<String>,
     #[arg(long, default_value = "standard", value_parser = parse_base64_format)]
     pub format: Base64Format,
+    /// Wrap lines at this many columns, 76 for MIME and 64 for PEM
+    #[arg(long, default_value = "0")]
+    pub wrap: usize,
+    /// Use CRLF line endings when wrapping, as MIME requires
+    #[arg(long)]
+    pub crlf: bool,
+    /// Compress before encoding
+    #[arg(long, value_parser = parse_compress_format)]
+    pub compress: Option<CompressFormat>,
 }
 
 impl CmdExecutor for Base64EncodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
         // println!("Encode base64: {:?}", base64_encode_opts);
-        let encoded = process_base64_encode(self)?;
-        println!("{}", encoded);
-        Ok(())
+        let writer = get_writer(self.output.as_deref().unwrap_or("-"))?;
+        process_base64_encode(self, writer)
     }
 }
 
@@ -40,21 +61,86 @@ impl CmdExecutor for Base64EncodeOpts {
 pub struct Base64DecodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
+    /// Write the raw bytes to a file, or to stdout with "-"
+    #[arg(short, long)]
+    pub output: Option<String>,
     #[arg(long, default_value = "standard", value_parser = parse_base64_format)]
     pub format: Base64Format,
+    /// Detect alphabet and padding, ignore whitespace and missing padding
+    #[arg(long, alias = "auto")]
+    pub lenient: bool,
+    /// Decompress after decoding
+    #[arg(long, value_parser = parse_compress_format)]
+    pub decompress: Option<CompressFormat>,
 }
 
 impl CmdExecutor for Base64DecodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
         // println!("Decode base64: {:?}", base64_decode_opts);
-        let decoded = process_base64_decode(self)?;
-        let decoded = String::from_utf8(decoded)?;
-        println!("{}", decoded);
+        // a file, pipe or redirect gets the raw bytes streamed, only the
+        // terminal preview needs all of them
+        let output = match &self.output {
+            None if !std::io::stdout().is_terminal() => Some("-".to_string()),
+            output => output.clone(),
+        };
+        let lenient = self.lenient;
+        let mut decoded = Vec::new();
+        let format = match &output {
+            Some(output) => process_base64_decode(self, get_writer(output)?)?,
+            None => process_base64_decode(self, &mut decoded)?,
+        };
+        if lenient {
+            eprintln!("Detected base64 variant: {}", format);
+        }
+        match output {
+            Some(_) => Ok(()),
+            None => write_decoded(None, decoded),
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct Base64DataUriOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// MIME type to use instead of sniffing it from the content or extension
+    #[arg(long)]
+    pub mime: Option<String>,
+}
+
+impl CmdExecutor for Base64DataUriOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let output = self.output.clone();
+        let uri = process_datauri_encode(self)?;
+        match output {
+            Some(output) => writeln!(get_writer(&output)?, "{}", uri)?,
+            None => println!("{}", uri),
+        }
         Ok(())
     }
 }
 
-fn parse_base64_format(format: &str) -> Result<Base64Format, anyhow::Error> {
+#[derive(Debug, Parser)]
+pub struct Base64DataUriDecodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// Write the payload to a file, or to stdout with "-"
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+impl CmdExecutor for Base64DataUriDecodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let output = self.output.clone();
+        let uri = process_datauri_decode(self)?;
+        eprintln!("MIME type: {}", uri.mime);
+        write_decoded(output.as_deref(), uri.data)
+    }
+}
+
+pub fn parse_base64_format(format: &str) -> Result<Base64Format, anyhow::Error> {
     format.parse()
 }
 
@@ -64,7 +150,9 @@ impl FromStr for Base64Format {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "urlsafe" => Ok(Base64Format::UrlSafe),
+            "urlsafe-pad" => Ok(Base64Format::UrlSafePad),
             "standard" => Ok(Base64Format::Standard),
+            "standard-nopad" => Ok(Base64Format::StandardNoPad),
             _ => anyhow::bail!("Invalid base64 format: {}", s),
         }
     }
@@ -74,7 +162,9 @@ impl From<Base64Format> for &'static str {
     fn from(format: Base64Format) -> Self {
         match format {
             Base64Format::UrlSafe => "urlsafe",
+            Base64Format::UrlSafePad => "urlsafe-pad",
             Base64Format::Standard => "standard",
+            Base64Format::StandardNoPad => "standard-nopad",
         }
     }
 }
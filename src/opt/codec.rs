@@ -0,0 +1,132 @@
+use std::io::IsTerminal;
+use std::{fmt::Display, str::FromStr};
+
+use crate::{CmdExecutor, get_writer, process_decode, process_encode, verify_file, write_decoded};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct EncodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    #[arg(short, long, value_parser = parse_codec_format)]
+    pub codec: CodecFormat,
+}
+
+impl CmdExecutor for EncodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let writer = get_writer(self.output.as_deref().unwrap_or("-"))?;
+        process_encode(self, writer)
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct DecodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// Write the raw bytes to a file, or to stdout with "-"
+    #[arg(short, long)]
+    pub output: Option<String>,
+    #[arg(short, long, value_parser = parse_codec_format, required_unless_present = "auto")]
+    pub codec: Option<CodecFormat>,
+    /// Detect and peel nested layers until plain text or binary is left
+    #[arg(long, conflicts_with = "codec")]
+    pub auto: bool,
+}
+
+impl CmdExecutor for DecodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        // a file, pipe or redirect gets the raw bytes streamed, only the
+        // terminal preview needs all of them
+        let output = match &self.output {
+            None if !std::io::stdout().is_terminal() => Some("-".to_string()),
+            output => output.clone(),
+        };
+        let mut decoded = Vec::new();
+        let layers = match &output {
+            Some(output) => process_decode(self, get_writer(output)?)?,
+            None => process_decode(self, &mut decoded)?,
+        };
+        if !layers.is_empty() {
+            eprintln!("Layers: {}", layers.join(" -> "));
+        }
+        match output {
+            Some(_) => Ok(()),
+            None => write_decoded(None, decoded),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodecFormat {
+    Hex,
+    HexUpper,
+    Base32,
+    Base32Crockford,
+    Base58,
+    Base58Check,
+    Base64,
+    Base64Url,
+    Base85,
+    Ascii85,
+    Z85,
+    Percent,
+}
+
+impl CodecFormat {
+    pub const ALL: [CodecFormat; 12] = [
+        CodecFormat::Hex,
+        CodecFormat::HexUpper,
+        CodecFormat::Base32,
+        CodecFormat::Base32Crockford,
+        CodecFormat::Base58,
+        CodecFormat::Base58Check,
+        CodecFormat::Base64,
+        CodecFormat::Base64Url,
+        CodecFormat::Base85,
+        CodecFormat::Ascii85,
+        CodecFormat::Z85,
+        CodecFormat::Percent,
+    ];
+}
+
+fn parse_codec_format(format: &str) -> Result<CodecFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for CodecFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CodecFormat::ALL
+            .into_iter()
+            .find(|format| <&str>::from(*format) == s.to_lowercase())
+            .ok_or_else(|| anyhow::anyhow!("Invalid codec: {}", s))
+    }
+}
+
+impl From<CodecFormat> for &'static str {
+    fn from(format: CodecFormat) -> Self {
+        match format {
+            CodecFormat::Hex => "hex",
+            CodecFormat::HexUpper => "hex-upper",
+            CodecFormat::Base32 => "base32",
+            CodecFormat::Base32Crockford => "base32-crockford",
+            CodecFormat::Base58 => "base58",
+            CodecFormat::Base58Check => "base58check",
+            CodecFormat::Base64 => "base64",
+            CodecFormat::Base64Url => "base64url",
+            CodecFormat::Base85 => "base85",
+            CodecFormat::Ascii85 => "ascii85",
+            CodecFormat::Z85 => "z85",
+            CodecFormat::Percent => "percent",
+        }
+    }
+}
+
+impl Display for CodecFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -0,0 +1,90 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{CmdExecutor, get_writer, process_compress, process_decompress, verify_file};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CompressOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(short, long, default_value = "gzip", value_parser = parse_compress_format)]
+    pub format: CompressFormat,
+    /// 0-9 for gzip and deflate, 1-22 for zstd, 0-11 for brotli
+    #[arg(short, long)]
+    pub level: Option<u32>,
+}
+
+impl CmdExecutor for CompressOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let writer = get_writer(&self.output)?;
+        let stats = process_compress(self, writer)?;
+        eprintln!("{}", stats);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct DecompressOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Detected from the magic bytes if omitted, which brotli doesn't have
+    #[arg(short, long, value_parser = parse_compress_format)]
+    pub format: Option<CompressFormat>,
+}
+
+impl CmdExecutor for DecompressOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let writer = get_writer(&self.output)?;
+        let stats = process_decompress(self, writer)?;
+        eprintln!("{}", stats);
+        Ok(())
+    }
+}
+
+/// `Deflate` is the zlib wrapped stream that HTTP calls deflate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressFormat {
+    Gzip,
+    Zstd,
+    Brotli,
+    Deflate,
+}
+
+pub fn parse_compress_format(format: &str) -> Result<CompressFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for CompressFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gzip" | "gz" => Ok(CompressFormat::Gzip),
+            "zstd" | "zst" => Ok(CompressFormat::Zstd),
+            "brotli" | "br" => Ok(CompressFormat::Brotli),
+            "deflate" | "zlib" => Ok(CompressFormat::Deflate),
+            v => anyhow::bail!("Invalid compression format: {}", v),
+        }
+    }
+}
+
+impl From<CompressFormat> for &'static str {
+    fn from(format: CompressFormat) -> Self {
+        match format {
+            CompressFormat::Gzip => "gzip",
+            CompressFormat::Zstd => "zstd",
+            CompressFormat::Brotli => "brotli",
+            CompressFormat::Deflate => "deflate",
+        }
+    }
+}
+
+impl Display for CompressFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
@@ -1,18 +1,26 @@
 mod base64;
+mod codec;
+mod compress;
 mod csv;
 mod http;
+mod otp;
 mod pwd;
+mod random;
 mod text;
 
 use std::path::{Path, PathBuf};
 
 pub use base64::*;
+pub use codec::*;
+pub use compress::*;
 pub use csv::*;
 pub use http::*;
+pub use otp::*;
 pub use pwd::*;
+pub use random::*;
 pub use text::*;
 
-use clap::{Parser, command};
+use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
 #[derive(Debug, Parser)]
@@ -31,17 +39,50 @@ pub enum Subcommand {
     )]
     Csv(CsvOpts),
 
-    #[command(name = "pwd", about = "Generate a random password")]
-    Pwd(PwdOpts),
+    #[command(name = "pwd", about = "Generate or check passwords")]
+    Pwd(PwdCommand),
 
     #[command(subcommand, about = "Base64 encoding and decoding")]
     Base64(Base64SubCommand),
 
+    #[command(
+        name = "encode",
+        about = "Encode data with hex, base32, base58, base85, ..."
+    )]
+    Encode(EncodeOpts),
+
+    #[command(
+        name = "decode",
+        about = "Decode data encoded with hex, base32, base58, base85, ..."
+    )]
+    Decode(DecodeOpts),
+
+    #[command(
+        name = "compress",
+        about = "Compress with gzip, zstd, brotli or deflate"
+    )]
+    Compress(CompressOpts),
+
+    #[command(
+        name = "decompress",
+        about = "Decompress gzip, zstd, brotli or deflate"
+    )]
+    Decompress(DecompressOpts),
+
     #[command(subcommand, about = "Text sign/verify")]
     Text(TextSubCommand),
 
     #[command(subcommand, about = "HTTP server")]
     Http(HttpSubCommand),
+
+    #[command(subcommand, about = "TOTP/HOTP one-time passwords")]
+    Otp(OtpSubCommand),
+
+    #[command(
+        subcommand,
+        about = "Random bytes, UUIDs, ULIDs, NanoIDs and API tokens"
+    )]
+    Rand(RandSubCommand),
 }
 
 pub fn verify_file(file_name: &str) -> Result<String, &'static str> {
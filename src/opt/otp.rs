@@ -0,0 +1,177 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    CmdExecutor, process_otp_generate, process_otp_hotp, process_otp_totp, process_otp_verify,
+    verify_file,
+};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum OtpSubCommand {
+    #[command(name = "totp", about = "Generate a time based one-time password")]
+    Totp(OtpTotpOpts),
+    #[command(name = "hotp", about = "Generate a counter based one-time password")]
+    Hotp(OtpHotpOpts),
+    #[command(name = "verify", about = "Verify a one-time password")]
+    Verify(OtpVerifyOpts),
+    #[command(name = "generate", about = "Generate a new secret and otpauth:// URI")]
+    Generate(OtpGenerateOpts),
+}
+
+// The secret (base32 or a whole otpauth:// URI) is read from `input`, never
+// from argv. Unset options fall back to the URI, then to the RFC defaults.
+#[derive(Debug, Parser)]
+pub struct OtpParams {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(long, value_parser = parse_otp_algorithm)]
+    pub algorithm: Option<OtpAlgorithm>,
+
+    #[arg(long)]
+    pub digits: Option<u32>,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpTotpOpts {
+    #[command(flatten)]
+    pub params: OtpParams,
+
+    #[arg(long)]
+    pub period: Option<u64>,
+
+    /// Unix time to generate the code for, defaults to now
+    #[arg(long)]
+    pub time: Option<u64>,
+}
+
+impl CmdExecutor for OtpTotpOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let code = process_otp_totp(self)?;
+        println!("{}", code);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpHotpOpts {
+    #[command(flatten)]
+    pub params: OtpParams,
+
+    #[arg(long)]
+    pub counter: Option<u64>,
+}
+
+impl CmdExecutor for OtpHotpOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let code = process_otp_hotp(self)?;
+        println!("{}", code);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpVerifyOpts {
+    #[command(flatten)]
+    pub params: OtpParams,
+
+    #[arg(long)]
+    pub code: String,
+
+    #[arg(long)]
+    pub period: Option<u64>,
+
+    #[arg(long)]
+    pub time: Option<u64>,
+
+    /// Verify as HOTP starting at this counter instead of TOTP
+    #[arg(long)]
+    pub counter: Option<u64>,
+
+    /// Accepted clock skew in periods for TOTP, look-ahead for HOTP
+    #[arg(long, default_value = "1")]
+    pub window: u64,
+}
+
+impl CmdExecutor for OtpVerifyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        match process_otp_verify(self)? {
+            Some(offset) => println!("Code is valid (offset {})", offset),
+            None => anyhow::bail!("Code is not valid"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpGenerateOpts {
+    #[arg(long)]
+    pub issuer: Option<String>,
+
+    #[arg(long, default_value = "rcli")]
+    pub account: String,
+
+    #[arg(long, default_value = "sha1", value_parser = parse_otp_algorithm)]
+    pub algorithm: OtpAlgorithm,
+
+    #[arg(long, default_value = "6")]
+    pub digits: u32,
+
+    #[arg(long, default_value = "30")]
+    pub period: u64,
+
+    /// Secret length in bytes
+    #[arg(long, default_value = "20")]
+    pub bytes: usize,
+}
+
+impl CmdExecutor for OtpGenerateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let (secret, uri) = process_otp_generate(self)?;
+        println!("{}", secret);
+        println!("{}", uri);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+fn parse_otp_algorithm(algorithm: &str) -> Result<OtpAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sha1" => Ok(OtpAlgorithm::Sha1),
+            "sha256" => Ok(OtpAlgorithm::Sha256),
+            "sha512" => Ok(OtpAlgorithm::Sha512),
+            v => anyhow::bail!("Invalid OTP algorithm: {}", v),
+        }
+    }
+}
+
+impl From<OtpAlgorithm> for &'static str {
+    fn from(algorithm: OtpAlgorithm) -> Self {
+        match algorithm {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+impl Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
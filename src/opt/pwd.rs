@@ -1,32 +1,401 @@
-use crate::{CmdExecutor, process_pwd};
-use clap::Parser;
-use zxcvbn::zxcvbn;
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    CmdExecutor, process_pwd_batch, process_pwd_bench, process_pwd_check, process_pwd_derive,
+    process_pwd_hash, process_pwd_verify, verify_file,
+};
+use clap::{ArgAction, Parser};
+use enum_dispatch::enum_dispatch;
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct PwdCommand {
+    #[command(subcommand)]
+    pub cmd: Option<PwdSubCommand>,
+
+    #[command(flatten)]
+    pub opts: PwdGenerateOpts,
+}
+
+impl CmdExecutor for PwdCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        match self.cmd {
+            Some(cmd) => cmd.execute().await,
+            None => self.opts.execute().await,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum PwdSubCommand {
+    #[command(
+        name = "check",
+        about = "Analyze the strength of passwords read from stdin or a file"
+    )]
+    Check(PwdCheckOpts),
+    #[command(
+        name = "derive",
+        about = "Derive a site specific password from a master secret"
+    )]
+    Derive(PwdDeriveOpts),
+    #[command(name = "hash", about = "Hash a password into a PHC string")]
+    Hash(PwdHashOpts),
+    #[command(name = "verify", about = "Verify a password against a PHC string")]
+    Verify(PwdVerifyOpts),
+    #[command(
+        name = "bench",
+        about = "Calibrate Argon2id parameters to a target duration"
+    )]
+    Bench(PwdBenchOpts),
+}
+
+#[derive(Debug, Clone, Parser)]
 pub struct PwdOpts {
     #[arg(short, long, default_value = "16")]
     pub length: u8,
 
-    #[arg(long, default_value = "true")]
+    /// At least one of the uppercase letters, `--uppercase false` leaves them out
+    #[arg(long, default_value = "true", num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
     pub uppercase: bool,
 
-    #[arg(long, default_value = "true")]
+    /// At least one of the lowercase letters, `--lowercase false` leaves them out
+    #[arg(long, default_value = "true", num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
     pub lowercase: bool,
 
-    #[arg(long, default_value = "true")]
+    /// At least one of the digits, `--numbers false` leaves them out
+    #[arg(long, default_value = "true", num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
     pub numbers: bool,
 
-    #[arg(long, default_value = "true")]
+    /// At least one of the symbols, `--symbols false` leaves them out
+    #[arg(long, default_value = "true", num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
     pub symbols: bool,
+
+    /// Build the password from syllables that are easy to read out loud
+    #[arg(long)]
+    pub pronounceable: bool,
+
+    /// Reject passwords found in a sorted HIBP SHA-1 dump
+    #[arg(long, value_parser = verify_file)]
+    pub breach_db: Option<String>,
+
+    // Reproducible output for tests only, a known seed is not a secret
+    #[arg(long, hide = true)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct PwdGenerateOpts {
+    #[command(flatten)]
+    pub policy: PwdOpts,
+
+    #[arg(short, long, default_value = "1")]
+    pub count: usize,
+
+    #[arg(long, default_value = "text", value_parser = parse_pwd_output_format)]
+    pub format: PwdOutputFormat,
 }
 
-impl CmdExecutor for PwdOpts {
+impl CmdExecutor for PwdGenerateOpts {
     async fn execute(self) -> anyhow::Result<()> {
         // println!("Generate password: {:?}", pwd_opts);
-        let password = process_pwd(self)?;
+        let entries = process_pwd_batch(self.policy, self.count)?;
+        match self.format {
+            PwdOutputFormat::Text => {
+                for entry in &entries {
+                    println!("{}", entry.password);
+                }
+                if let [entry] = entries.as_slice() {
+                    eprintln!("Password strength: {}", entry.score);
+                    eprintln!("Password entropy: {:.1} bits", entry.entropy_bits);
+                }
+            }
+            PwdOutputFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+            PwdOutputFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(std::io::stdout());
+                writer.write_record(["password", "length", "classes", "entropy_bits", "score"])?;
+                for entry in &entries {
+                    writer.write_record([
+                        entry.password.clone(),
+                        entry.length.to_string(),
+                        entry.classes.join("+"),
+                        format!("{:.1}", entry.entropy_bits),
+                        entry.score.to_string(),
+                    ])?;
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct PwdDeriveOpts {
+    #[arg(long)]
+    pub site: String,
+
+    #[arg(long, default_value = "")]
+    pub user: String,
+
+    /// Bump to rotate the password for the same site and user
+    #[arg(long, default_value = "1")]
+    pub counter: u32,
+
+    /// Read the master secret from a file instead of prompting for it
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: Option<String>,
+
+    #[command(flatten)]
+    pub policy: PwdOpts,
+}
+
+impl CmdExecutor for PwdDeriveOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let password = process_pwd_derive(self)?;
         println!("{}", password);
-        let result = zxcvbn(&password, &[]);
-        eprintln!("Password strength: {}", result.score());
         Ok(())
     }
 }
+
+// Passwords are only ever read from `input` (one per line) so they never
+// end up in shell history or the process list.
+#[derive(Debug, Parser)]
+pub struct PwdCheckOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    /// Context words (user name, site, ...) that make a password weaker
+    #[arg(short, long = "word")]
+    pub words: Vec<String>,
+
+    /// Look passwords up in a sorted HIBP SHA-1 dump
+    #[arg(long, value_parser = verify_file)]
+    pub breach_db: Option<String>,
+
+    #[arg(long, default_value = "text", value_parser = parse_pwd_check_format)]
+    pub format: PwdCheckFormat,
+}
+
+impl CmdExecutor for PwdCheckOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = self.format;
+        let reports = process_pwd_check(self)?;
+        match format {
+            PwdCheckFormat::Text => {
+                for (i, report) in reports.iter().enumerate() {
+                    println!("Password #{}", i + 1);
+                    print!("{}", report);
+                }
+            }
+            PwdCheckFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct PwdHashOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(long, default_value = "argon2id", value_parser = parse_pwd_hash_algorithm)]
+    pub algorithm: PwdHashAlgorithm,
+
+    /// Argon2 memory in KiB
+    #[arg(long, default_value = "19456")]
+    pub m_cost: u32,
+
+    /// Argon2 iterations
+    #[arg(long, default_value = "2")]
+    pub t_cost: u32,
+
+    /// Argon2 parallelism
+    #[arg(long, default_value = "1")]
+    pub p_cost: u32,
+
+    /// bcrypt cost
+    #[arg(long, default_value = "12")]
+    pub cost: u32,
+
+    /// scrypt log2(N)
+    #[arg(long, default_value = "17")]
+    pub log_n: u8,
+
+    /// scrypt block size
+    #[arg(long, default_value = "8")]
+    pub scrypt_r: u32,
+
+    /// scrypt parallelism
+    #[arg(long, default_value = "1")]
+    pub scrypt_p: u32,
+}
+
+impl CmdExecutor for PwdHashOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let hash = process_pwd_hash(self)?;
+        println!("{}", hash);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct PwdVerifyOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(long)]
+    pub hash: String,
+}
+
+impl CmdExecutor for PwdVerifyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if !process_pwd_verify(self)? {
+            anyhow::bail!("Password does not match");
+        }
+        println!("Password matches");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct PwdBenchOpts {
+    #[arg(long, default_value = "500")]
+    pub target_ms: u64,
+
+    /// Upper bound for Argon2 memory in KiB before iterations are raised
+    #[arg(long, default_value = "1048576")]
+    pub max_m_cost: u32,
+
+    #[arg(long, default_value = "1")]
+    pub t_cost: u32,
+
+    #[arg(long, default_value = "1")]
+    pub p_cost: u32,
+}
+
+impl CmdExecutor for PwdBenchOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let result = process_pwd_bench(self)?;
+        println!("{}", result);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PwdHashAlgorithm {
+    Argon2id,
+    Bcrypt,
+    Scrypt,
+}
+
+fn parse_pwd_hash_algorithm(algorithm: &str) -> Result<PwdHashAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl FromStr for PwdHashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "argon2id" => Ok(PwdHashAlgorithm::Argon2id),
+            "bcrypt" => Ok(PwdHashAlgorithm::Bcrypt),
+            "scrypt" => Ok(PwdHashAlgorithm::Scrypt),
+            v => anyhow::bail!("Invalid hash algorithm: {}", v),
+        }
+    }
+}
+
+impl From<PwdHashAlgorithm> for &'static str {
+    fn from(algorithm: PwdHashAlgorithm) -> Self {
+        match algorithm {
+            PwdHashAlgorithm::Argon2id => "argon2id",
+            PwdHashAlgorithm::Bcrypt => "bcrypt",
+            PwdHashAlgorithm::Scrypt => "scrypt",
+        }
+    }
+}
+
+impl Display for PwdHashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PwdOutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+fn parse_pwd_output_format(format: &str) -> Result<PwdOutputFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for PwdOutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(PwdOutputFormat::Text),
+            "json" => Ok(PwdOutputFormat::Json),
+            "csv" => Ok(PwdOutputFormat::Csv),
+            v => anyhow::bail!("Invalid output format: {}", v),
+        }
+    }
+}
+
+impl From<PwdOutputFormat> for &'static str {
+    fn from(format: PwdOutputFormat) -> Self {
+        match format {
+            PwdOutputFormat::Text => "text",
+            PwdOutputFormat::Json => "json",
+            PwdOutputFormat::Csv => "csv",
+        }
+    }
+}
+
+impl Display for PwdOutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+// pwd check reports are nested, so there is no CSV form
+#[derive(Debug, Clone, Copy)]
+pub enum PwdCheckFormat {
+    Text,
+    Json,
+}
+
+fn parse_pwd_check_format(format: &str) -> Result<PwdCheckFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for PwdCheckFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(PwdCheckFormat::Text),
+            "json" => Ok(PwdCheckFormat::Json),
+            v => anyhow::bail!("Invalid output format: {}", v),
+        }
+    }
+}
+
+impl From<PwdCheckFormat> for &'static str {
+    fn from(format: PwdCheckFormat) -> Self {
+        match format {
+            PwdCheckFormat::Text => "text",
+            PwdCheckFormat::Json => "json",
+        }
+    }
+}
+
+impl Display for PwdCheckFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
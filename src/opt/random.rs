@@ -0,0 +1,152 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    Base64Format, CmdExecutor, parse_base64_format, process_rand_bytes, process_rand_check_token,
+    process_rand_nanoid, process_rand_token, process_rand_ulid, process_rand_uuid, verify_file,
+};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum RandSubCommand {
+    #[command(name = "bytes", about = "Random bytes as hex or base64")]
+    Bytes(RandBytesOpts),
+    #[command(name = "uuid", about = "Random UUID (v4) or time ordered UUID (v7)")]
+    Uuid(RandUuidOpts),
+    #[command(name = "ulid", about = "Time ordered ULID")]
+    Ulid(RandUlidOpts),
+    #[command(name = "nanoid", about = "NanoID with an optional custom alphabet")]
+    Nanoid(RandNanoidOpts),
+    #[command(name = "token", about = "Prefixed API token with a CRC32 checksum")]
+    Token(RandTokenOpts),
+    #[command(name = "check-token", about = "Validate the checksum of an API token")]
+    CheckToken(RandCheckTokenOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct RandBytesOpts {
+    #[arg(short, long, default_value = "32")]
+    pub length: usize,
+
+    #[arg(long, default_value = "hex", value_parser = parse_rand_encoding)]
+    pub encoding: RandEncoding,
+
+    #[arg(long, default_value = "standard", value_parser = parse_base64_format)]
+    pub format: Base64Format,
+}
+
+impl CmdExecutor for RandBytesOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        println!("{}", process_rand_bytes(self)?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct RandUuidOpts {
+    #[arg(long, default_value = "4", value_parser = ["4", "7"])]
+    pub version: String,
+}
+
+impl CmdExecutor for RandUuidOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        println!("{}", process_rand_uuid(self)?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct RandUlidOpts {}
+
+impl CmdExecutor for RandUlidOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        println!("{}", process_rand_ulid(self)?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct RandNanoidOpts {
+    #[arg(short, long, default_value = "21")]
+    pub length: usize,
+
+    #[arg(
+        long,
+        default_value = "_-0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"
+    )]
+    pub alphabet: String,
+}
+
+impl CmdExecutor for RandNanoidOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        println!("{}", process_rand_nanoid(self)?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct RandTokenOpts {
+    #[arg(long, default_value = "rcli")]
+    pub prefix: String,
+}
+
+impl CmdExecutor for RandTokenOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        println!("{}", process_rand_token(self)?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct RandCheckTokenOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+}
+
+impl CmdExecutor for RandCheckTokenOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if !process_rand_check_token(self)? {
+            anyhow::bail!("Token checksum is invalid");
+        }
+        println!("Token checksum is valid");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RandEncoding {
+    Hex,
+    Base64,
+}
+
+fn parse_rand_encoding(encoding: &str) -> Result<RandEncoding, anyhow::Error> {
+    encoding.parse()
+}
+
+impl FromStr for RandEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hex" => Ok(RandEncoding::Hex),
+            "base64" => Ok(RandEncoding::Base64),
+            v => anyhow::bail!("Invalid encoding: {}", v),
+        }
+    }
+}
+
+impl From<RandEncoding> for &'static str {
+    fn from(encoding: RandEncoding) -> Self {
+        match encoding {
+            RandEncoding::Hex => "hex",
+            RandEncoding::Base64 => "base64",
+        }
+    }
+}
+
+impl Display for RandEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
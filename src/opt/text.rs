@@ -1,12 +1,18 @@
 use crate::verify_path;
 use crate::{
-    CmdExecutor, process_text_generate, process_text_sign, process_text_verify, verify_file,
+    CmdExecutor, Exit, KdfParams, KeyFile, KeyKind, VerifyResult, X25519Identity,
+    create_secret_file, get_secret_writer, get_writer, process_text_decrypt, process_text_encrypt,
+    process_text_generate, process_text_key_export, process_text_key_import,
+    process_text_key_x25519, process_text_open, process_text_seal, process_text_sign,
+    process_text_sign_tree, process_text_verify, process_text_verify_tree, verify_file,
+    write_secret_output,
 };
 use anyhow::anyhow;
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 use std::fmt::Display;
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
 use std::str::FromStr;
 
@@ -15,10 +21,54 @@ use std::str::FromStr;
 pub enum TextSubCommand {
     #[command(name = "sign", about = "Sign text with private/shared key")]
     Sign(TextSignOpts),
-    #[command(name = "verify", about = "Verify signature of text")]
+    #[command(
+        name = "verify",
+        about = "Verify signature of text",
+        after_help = "Exit status: 0 valid, 1 mismatch, 2 usage error, 3 malformed signature, 4 unusable key, 5 unreadable input"
+    )]
     Verify(TextVerifyOpts),
     #[command(name = "generate", about = "Generate a new key")]
     Generate(TextKeyGenerateOpts),
+    #[command(
+        name = "sign-tree",
+        about = "Sign a manifest of every file in a directory"
+    )]
+    SignTree(TextSignTreeOpts),
+    #[command(
+        name = "verify-tree",
+        about = "Verify a directory against its signed manifest"
+    )]
+    VerifyTree(TextVerifyTreeOpts),
+    #[command(name = "encrypt", about = "Encrypt with a symmetric key")]
+    Encrypt(TextEncryptOpts),
+    #[command(name = "decrypt", about = "Decrypt a message from text encrypt")]
+    Decrypt(TextDecryptOpts),
+    #[command(name = "seal", about = "Encrypt to age X25519 recipients")]
+    Seal(TextSealOpts),
+    #[command(name = "open", about = "Decrypt an age file with an X25519 identity")]
+    Open(TextOpenOpts),
+    #[command(subcommand, name = "key", about = "Import or export keys")]
+    Key(TextKeySubCommand),
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum TextKeySubCommand {
+    #[command(
+        name = "export",
+        about = "Convert a key to PEM, DER, OpenSSH, JWK or raw bytes"
+    )]
+    Export(TextKeyExportOpts),
+    #[command(
+        name = "import",
+        about = "Convert a PEM, DER, OpenSSH, JWK or raw key to an rcli key file"
+    )]
+    Import(TextKeyImportOpts),
+    #[command(
+        name = "x25519",
+        about = "Convert an Ed25519 key to an age X25519 identity or recipient"
+    )]
+    X25519(TextKeyX25519Opts),
 }
 
 #[derive(Debug, Parser)]
@@ -50,52 +100,499 @@ pub struct TextVerifyOpts {
     pub key: String,
     #[arg(short, long)]
     pub signature: String,
-    #[arg(long, default_value = "blake3", value_parser = parse_text_format)]
-    pub format: TextSignFormat,
+    /// The key decides BLAKE3 or Ed25519, a raw 32 byte key needs this to be a
+    /// BLAKE3 key. Must agree with the tag of a tagged signature
+    #[arg(long, value_parser = parse_text_format)]
+    pub format: Option<TextSignFormat>,
+    /// Print a JSON report with the algorithm, key id, input digest and result
+    #[arg(long)]
+    pub json: bool,
 }
 
 impl CmdExecutor for TextVerifyOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        //println!("Text Verify: {:?}", opts);
-        let verified = process_text_verify(self)?;
-        print!("{}", verified);
+        let json = self.json;
+        let report = process_text_verify(self);
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else if let Some(error) = &report.error {
+            eprintln!("Error: {}", error);
+        } else {
+            println!("{}", report.result == VerifyResult::Valid);
+        }
+        match report.result.exit_code() {
+            0 => Ok(()),
+            code => Err(Exit(code).into()),
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TextSignTreeOpts {
+    #[arg(value_parser = verify_path)]
+    pub dir: PathBuf,
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+    #[arg(long, default_value = "ed25519", value_parser = parse_text_format)]
+    pub format: TextSignFormat,
+    /// Where to write the manifest, rcli-manifest.txt in the directory by default
+    #[arg(short, long)]
+    pub manifest: Option<PathBuf>,
+}
+
+impl CmdExecutor for TextSignTreeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let (path, manifest) = process_text_sign_tree(self)?;
+        println!(
+            "Signed {} files into {}",
+            manifest.entries.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TextVerifyTreeOpts {
+    #[arg(value_parser = verify_path)]
+    pub dir: PathBuf,
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+    #[arg(short, long)]
+    pub manifest: Option<PathBuf>,
+    /// The key decides BLAKE3 or Ed25519, a raw 32 byte key needs this to be a
+    /// BLAKE3 key. Must agree with the manifest's signature tag
+    #[arg(long, value_parser = parse_text_format)]
+    pub format: Option<TextSignFormat>,
+}
+
+impl CmdExecutor for TextVerifyTreeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let report = process_text_verify_tree(self)?;
+        println!("{}", report);
+        if !report.is_ok() {
+            anyhow::bail!("Directory doesn't match its signed manifest");
+        }
         Ok(())
     }
 }
 
+#[derive(Debug, Parser)]
+pub struct TextEncryptOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Symmetric key from `text generate --format symmetric`
+    #[arg(short, long, value_parser = verify_file, required_unless_present_any = ["passphrase", "passphrase_fd"])]
+    pub key: Option<String>,
+    /// Encrypt with a passphrase prompted for without echo
+    #[arg(long, conflicts_with = "key")]
+    pub passphrase: bool,
+    /// Read the passphrase from the first line of this file descriptor
+    #[arg(long, conflicts_with = "key")]
+    pub passphrase_fd: Option<i32>,
+    #[arg(long, default_value = "xchacha20poly1305", value_parser = parse_text_cipher)]
+    pub cipher: TextCipher,
+    /// Associated data that isn't encrypted but must match to decrypt
+    #[arg(long)]
+    pub aad: Option<String>,
+    /// Argon2 memory in KiB, for passphrases
+    #[arg(long, default_value = "19456")]
+    pub m_cost: u32,
+    /// Argon2 iterations, for passphrases
+    #[arg(long, default_value = "2")]
+    pub t_cost: u32,
+    /// Argon2 parallelism, for passphrases
+    #[arg(long, default_value = "1")]
+    pub p_cost: u32,
+}
+
+impl TextEncryptOpts {
+    pub fn kdf_params(&self) -> anyhow::Result<KdfParams> {
+        KdfParams::new(self.m_cost, self.t_cost, self.p_cost)
+    }
+}
+
+impl CmdExecutor for TextEncryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        // bad costs have to fail before the output is created
+        self.kdf_params()?;
+        let writer = get_writer(&self.output)?;
+        process_text_encrypt(self, writer)
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TextDecryptOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Without a key the passphrase is prompted for
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: Option<String>,
+    /// Read the passphrase from the first line of this file descriptor
+    #[arg(long, conflicts_with = "key")]
+    pub passphrase_fd: Option<i32>,
+    #[arg(long)]
+    pub aad: Option<String>,
+}
+
+impl CmdExecutor for TextDecryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let output = self.output.clone();
+        write_secret_output(&output, |writer| process_text_decrypt(self, writer))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TextSealOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// An age1... recipient, a file of them, or an Ed25519 public key
+    #[arg(short, long = "recipient", required = true)]
+    pub recipients: Vec<String>,
+    /// Write the binary age format instead of the armored one
+    #[arg(long)]
+    pub binary: bool,
+}
+
+impl CmdExecutor for TextSealOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let writer = get_writer(&self.output)?;
+        process_text_seal(self, writer)
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TextOpenOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// age identity file or Ed25519 secret key
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+}
+
+impl CmdExecutor for TextOpenOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let output = self.output.clone();
+        write_secret_output(&output, |writer| process_text_open(self, writer))
+    }
+}
+
 #[derive(Debug, Parser)]
 pub struct TextKeyGenerateOpts {
-    #[arg(short, long, default_value = "blake3", value_parser = parse_text_format)]
-    pub format: TextSignFormat,
+    #[arg(short, long, default_value = "blake3", value_parser = parse_key_type)]
+    pub format: TextKeyType,
     #[arg(short, long, value_parser = verify_path)]
     pub output: PathBuf,
+    // Reproducible keys for tests only, never use it for real keys
+    #[arg(long, hide = true)]
+    pub seed: Option<u64>,
+}
+
+// A file written by `text key generate`.
+struct KeyOutput {
+    name: &'static str,
+    about: String,
+    contents: String,
+    secret: bool,
+}
+
+impl KeyOutput {
+    fn armored(name: &'static str, kind: KeyKind, key: Vec<u8>) -> anyhow::Result<Self> {
+        let key_file = KeyFile::new(kind, key)?;
+        Ok(Self {
+            name,
+            about: format!("{} key {}", kind, key_file.key_id),
+            contents: key_file.to_armored(),
+            secret: kind.is_secret(),
+        })
+    }
 }
 
 impl CmdExecutor for TextKeyGenerateOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        println!("Text key Generate: {:?}", self);
         let format = self.format;
         let output = self.output.clone();
-        let keys = process_text_generate(self)?;
-        println!("Keys: {:?}", keys);
-        match format {
-            TextSignFormat::Blake3 => {
-                let name = output.join("blake3.txt");
-                fs::write(name, &keys[0])?;
+        let mut keys = process_text_generate(self)?.into_iter();
+        let mut key = || keys.next().expect("one key per file");
+        let files = match format {
+            TextKeyType::Blake3 => vec![KeyOutput::armored("blake3.txt", KeyKind::Blake3, key())?],
+            TextKeyType::Ed25519 => vec![
+                KeyOutput::armored("ed25519.sk", KeyKind::Ed25519Secret, key())?,
+                KeyOutput::armored("ed25519.pk", KeyKind::Ed25519Public, key())?,
+            ],
+            TextKeyType::Symmetric => vec![KeyOutput::armored(
+                "symmetric.key",
+                KeyKind::Symmetric,
+                key(),
+            )?],
+            TextKeyType::X25519 => {
+                let identity = X25519Identity::from_bytes(key().try_into().expect("32 bytes"));
+                let recipient = identity.recipient();
+                vec![
+                    KeyOutput {
+                        name: "x25519.key",
+                        about: format!("X25519 identity for {}", recipient),
+                        contents: identity.to_identity_file(),
+                        secret: true,
+                    },
+                    KeyOutput {
+                        name: "x25519.pub",
+                        about: format!("X25519 recipient {}", recipient),
+                        contents: format!("{}\n", recipient),
+                        secret: false,
+                    },
+                ]
             }
-            TextSignFormat::Ed25519 => {
-                fs::write(output.join("ed25519.sk"), &keys[0])?;
-                fs::write(output.join("ed25519.pk"), &keys[1])?;
+        };
+        for file in files {
+            let path = output.join(file.name);
+            if file.secret {
+                create_secret_file(&path)?.write_all(file.contents.as_bytes())?;
+            } else {
+                fs::write(&path, file.contents)?;
             }
+            println!("Wrote {} to {}", file.about, path.display());
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Parser)]
+pub struct TextKeyExportOpts {
+    /// Ed25519 key in any supported format
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(long, default_value = "pem", value_parser = parse_key_format)]
+    pub to: KeyFormat,
+    /// Export only the public half of a private key, raw 32 byte input is
+    /// then a public key
+    #[arg(long)]
+    pub public: bool,
+}
+
+impl CmdExecutor for TextKeyExportOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut writer = if self.public {
+            get_writer(&self.output)?
+        } else {
+            get_secret_writer(&self.output)?
+        };
+        writer.write_all(&process_text_key_export(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TextKeyX25519Opts {
+    /// Ed25519 key in any supported format
+    #[arg(short, long, value_parser = verify_file)]
+    pub key: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Convert only the public half, raw 32 byte input is then a public key
+    #[arg(long)]
+    pub public: bool,
+}
+
+impl CmdExecutor for TextKeyX25519Opts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut writer = if self.public {
+            get_writer(&self.output)?
+        } else {
+            get_secret_writer(&self.output)?
+        };
+        writer.write_all(process_text_key_x25519(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TextKeyImportOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Import only the public half, raw 32 byte input is then a public key
+    #[arg(long)]
+    pub public: bool,
+}
+
+impl CmdExecutor for TextKeyImportOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let output = self.output.clone();
+        let (key_file, format) = process_text_key_import(self)?;
+        let mut writer = if key_file.kind.is_secret() {
+            get_secret_writer(&output)?
+        } else {
+            get_writer(&output)?
+        };
+        writer.write_all(key_file.to_armored().as_bytes())?;
+        eprintln!(
+            "Imported {} key {} from {}",
+            key_file.kind, key_file.key_id, format
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextCipher {
+    XChaCha20Poly1305,
+    Aes256Gcm,
+}
+
+impl TextCipher {
+    /// Random part of the STREAM nonce, the AEAD nonce minus 5 bytes of
+    /// chunk counter and last-chunk flag.
+    pub fn nonce_len(&self) -> usize {
+        match self {
+            TextCipher::XChaCha20Poly1305 => 19,
+            TextCipher::Aes256Gcm => 7,
+        }
+    }
+}
+
+fn parse_text_cipher(cipher: &str) -> Result<TextCipher, anyhow::Error> {
+    cipher.parse()
+}
+
+impl FromStr for TextCipher {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xchacha20poly1305" => Ok(TextCipher::XChaCha20Poly1305),
+            "aes256gcm" => Ok(TextCipher::Aes256Gcm),
+            _ => Err(anyhow!("Unknown cipher: {}", s)),
+        }
+    }
+}
+
+impl From<TextCipher> for &'static str {
+    fn from(cipher: TextCipher) -> Self {
+        match cipher {
+            TextCipher::XChaCha20Poly1305 => "xchacha20poly1305",
+            TextCipher::Aes256Gcm => "aes256gcm",
+        }
+    }
+}
+
+impl Display for TextCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+/// Keys `text generate` can create, `Symmetric` is for `text encrypt` and
+/// `X25519` for `text seal`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextKeyType {
+    Blake3,
+    Ed25519,
+    Symmetric,
+    X25519,
+}
+
+fn parse_key_type(key_type: &str) -> Result<TextKeyType, anyhow::Error> {
+    key_type.parse()
+}
+
+impl FromStr for TextKeyType {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(TextKeyType::Blake3),
+            // Ed25519ph signs with the same keys
+            "ed25519" | "ed25519ph" => Ok(TextKeyType::Ed25519),
+            "symmetric" => Ok(TextKeyType::Symmetric),
+            "x25519" => Ok(TextKeyType::X25519),
+            _ => Err(anyhow!("Unknown key type: {}", s)),
+        }
+    }
+}
+
+impl From<TextKeyType> for &'static str {
+    fn from(key_type: TextKeyType) -> Self {
+        match key_type {
+            TextKeyType::Blake3 => "blake3",
+            TextKeyType::Ed25519 => "ed25519",
+            TextKeyType::Symmetric => "symmetric",
+            TextKeyType::X25519 => "x25519",
+        }
+    }
+}
+
+impl Display for TextKeyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyFormat {
+    Raw,
+    Rcli,
+    Pem,
+    Der,
+    OpenSsh,
+    Jwk,
+}
+
+fn parse_key_format(format: &str) -> Result<KeyFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for KeyFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(KeyFormat::Raw),
+            "rcli" => Ok(KeyFormat::Rcli),
+            "pem" => Ok(KeyFormat::Pem),
+            "der" => Ok(KeyFormat::Der),
+            "openssh" => Ok(KeyFormat::OpenSsh),
+            "jwk" => Ok(KeyFormat::Jwk),
+            _ => Err(anyhow!("Unknown key format: {}", s)),
+        }
+    }
+}
+
+impl From<KeyFormat> for &'static str {
+    fn from(format: KeyFormat) -> Self {
+        match format {
+            KeyFormat::Raw => "raw",
+            KeyFormat::Rcli => "rcli",
+            KeyFormat::Pem => "pem",
+            KeyFormat::Der => "der",
+            KeyFormat::OpenSsh => "openssh",
+            KeyFormat::Jwk => "jwk",
+        }
+    }
+}
+
+impl Display for KeyFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+/// `Ed25519ph` signs a SHA-512 digest of the input (RFC 8032) so that large
+/// inputs can be streamed, pure `Ed25519` reads all of it into memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextSignFormat {
     Blake3,
     Ed25519,
+    Ed25519ph,
 }
 
 fn parse_text_format(format: &str) -> Result<TextSignFormat, anyhow::Error> {
@@ -108,6 +605,7 @@ impl FromStr for TextSignFormat {
         match s {
             "blake3" => Ok(TextSignFormat::Blake3),
             "ed25519" => Ok(TextSignFormat::Ed25519),
+            "ed25519ph" => Ok(TextSignFormat::Ed25519ph),
             _ => Err(anyhow!("Unknown format: {}", s)),
         }
     }
@@ -118,6 +616,7 @@ impl From<TextSignFormat> for &'static str {
         match format {
             TextSignFormat::Blake3 => "blake3",
             TextSignFormat::Ed25519 => "ed25519",
+            TextSignFormat::Ed25519ph => "ed25519ph",
         }
     }
 }
@@ -0,0 +1,575 @@
+use std::fmt::Display;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use bech32::{Bech32, Hrp};
+use chacha20poly1305::{
+    ChaCha20Poly1305,
+    aead::{Aead, KeyInit},
+};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::{RngCore, rngs::OsRng};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use super::encrypt::{ArmorBody, Opener, Sealer, open_chunks, seal_chunks};
+use crate::{
+    Base64Format, Base64Reader, Base64Writer, Ed25519Key, KeyKind, TextKeyX25519Opts, TextOpenOpts,
+    TextSealOpts, base64_decode, base64_encode, get_reader, parse_ed25519_key,
+};
+
+// https://age-encryption.org/v1
+const INTRO: &str = "age-encryption.org/v1";
+const X25519_LABEL: &str = "age-encryption.org/v1/X25519";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+const CHUNK_SIZE: usize = 64 * 1024;
+const COLUMNS: usize = 64;
+const FILE_KEY_LEN: usize = 16;
+const NONCE_LEN: usize = 16;
+const MAX_HEADER_LINE: u64 = 4096;
+const MAX_STANZAS: usize = 1024;
+
+/// An age X25519 identity (`AGE-SECRET-KEY-1...`).
+pub struct X25519Identity(StaticSecret);
+
+/// An age X25519 recipient (`age1...`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct X25519Recipient(PublicKey);
+
+struct Stanza {
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+impl X25519Identity {
+    pub fn from_bytes(secret: [u8; 32]) -> Self {
+        Self(StaticSecret::from(secret))
+    }
+
+    /// The X25519 half of an Ed25519 key, the same scalar on the Montgomery
+    /// form of the curve.
+    pub fn from_ed25519(key: &SigningKey) -> Self {
+        Self(StaticSecret::from(key.to_scalar_bytes()))
+    }
+
+    pub fn recipient(&self) -> X25519Recipient {
+        X25519Recipient(PublicKey::from(&self.0))
+    }
+
+    /// The identity in the `age-keygen` file format.
+    pub fn to_identity_file(&self) -> String {
+        format!(
+            "# created: {}\n# public key: {}\n{}\n",
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            self.recipient(),
+            self
+        )
+    }
+
+    // Ok(None) when the stanza isn't for this identity.
+    fn unwrap(&self, stanza: &Stanza) -> anyhow::Result<Option<Zeroizing<[u8; FILE_KEY_LEN]>>> {
+        if stanza.args[0] != "X25519" {
+            return Ok(None);
+        }
+        let share = match stanza.args.as_slice() {
+            [_, share] => base64_decode(Base64Format::StandardNoPad, share)?,
+            _ => anyhow::bail!("Invalid X25519 stanza"),
+        };
+        let share: [u8; 32] = share
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid X25519 share"))?;
+        if stanza.body.len() != FILE_KEY_LEN + 16 {
+            anyhow::bail!("Invalid X25519 stanza body");
+        }
+        let share = PublicKey::from(share);
+        let shared = self.0.diffie_hellman(&share);
+        if !shared.was_contributory() {
+            anyhow::bail!("X25519 share is a low order point");
+        }
+        let salt = [&share.as_bytes()[..], self.recipient().0.as_bytes()].concat();
+        let wrap_key = hkdf(&salt, shared.as_bytes(), X25519_LABEL.as_bytes());
+        let cipher = ChaCha20Poly1305::new((&*wrap_key).into());
+        match cipher.decrypt((&[0u8; 12]).into(), stanza.body.as_slice()) {
+            Ok(file_key) => {
+                let file_key = Zeroizing::new(file_key);
+                Ok(file_key.as_slice().try_into().ok().map(Zeroizing::new))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl X25519Recipient {
+    /// The X25519 public key of an Ed25519 key, matches
+    /// `X25519Identity::from_ed25519` of its secret key.
+    pub fn from_ed25519(key: &VerifyingKey) -> Self {
+        Self(PublicKey::from(key.to_montgomery().to_bytes()))
+    }
+
+    fn wrap(&self, file_key: &[u8; FILE_KEY_LEN]) -> anyhow::Result<Stanza> {
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let share = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&self.0);
+        if !shared.was_contributory() {
+            anyhow::bail!("Recipient is a low order point");
+        }
+        let salt = [&share.as_bytes()[..], self.0.as_bytes()].concat();
+        let wrap_key = hkdf(&salt, shared.as_bytes(), X25519_LABEL.as_bytes());
+        let body = ChaCha20Poly1305::new((&*wrap_key).into())
+            .encrypt((&[0u8; 12]).into(), file_key.as_slice())
+            .map_err(|_| anyhow::anyhow!("Failed to wrap the file key"))?;
+        Ok(Stanza {
+            args: vec![
+                "X25519".to_string(),
+                base64_encode(Base64Format::StandardNoPad, share.as_bytes()),
+            ],
+            body,
+        })
+    }
+}
+
+impl Display for X25519Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hrp = Hrp::parse_unchecked(IDENTITY_HRP);
+        let encoded =
+            bech32::encode_upper::<Bech32>(hrp, self.0.as_bytes()).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", encoded)
+    }
+}
+
+impl FromStr for X25519Identity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let secret = decode_bech32(s, IDENTITY_HRP)?;
+        Ok(Self::from_bytes(secret))
+    }
+}
+
+impl Display for X25519Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hrp = Hrp::parse_unchecked(RECIPIENT_HRP);
+        let encoded =
+            bech32::encode::<Bech32>(hrp, self.0.as_bytes()).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", encoded)
+    }
+}
+
+impl FromStr for X25519Recipient {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(PublicKey::from(decode_bech32(s, RECIPIENT_HRP)?)))
+    }
+}
+
+// age keys are plain Bech32 (not Bech32m) in a single case.
+fn decode_bech32(s: &str, hrp: &str) -> anyhow::Result<[u8; 32]> {
+    let checked = bech32::primitives::decode::CheckedHrpstring::new::<Bech32>(s)
+        .map_err(|e| anyhow::anyhow!("Invalid age key {}: {}", s, e))?;
+    if checked.hrp().to_lowercase() != hrp {
+        anyhow::bail!("Expected an age key starting with {}1", hrp);
+    }
+    checked
+        .byte_iter()
+        .collect::<Vec<u8>>()
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("age key must be 32 bytes"))
+}
+
+pub fn process_text_seal<W: Write>(opts: TextSealOpts, writer: W) -> anyhow::Result<()> {
+    let recipients = opts
+        .recipients
+        .iter()
+        .map(|r| read_recipients(r))
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .concat();
+    let mut reader = get_reader(&opts.input)?;
+    seal(&recipients, !opts.binary, &mut reader, writer)
+}
+
+pub fn process_text_open<W: Write>(opts: TextOpenOpts, writer: W) -> anyhow::Result<()> {
+    let identities = read_identities(&opts.key)?;
+    let reader = get_reader(&opts.input)?;
+    open(&identities, reader, writer)
+}
+
+/// Converts an Ed25519 key to an age identity file (for a secret key) or
+/// recipient (for a public key, or with `--public`).
+pub fn process_text_key_x25519(opts: TextKeyX25519Opts) -> anyhow::Result<String> {
+    let raw_kind = if opts.public {
+        KeyKind::Ed25519Public
+    } else {
+        KeyKind::Ed25519Secret
+    };
+    let (key, _) = parse_ed25519_key(&fs::read(&opts.key)?, raw_kind)?;
+    let key = if opts.public { key.to_public() } else { key };
+    let converted = match key {
+        Ed25519Key::Secret(key) => X25519Identity::from_ed25519(&key).to_identity_file(),
+        Ed25519Key::Public(key) => format!("{}\n", X25519Recipient::from_ed25519(&key)),
+    };
+    Ok(converted)
+}
+
+/// Recipients from an `age1...` string, a recipients file with one per line,
+/// or an Ed25519 public key in any supported format.
+pub fn read_recipients(arg: &str) -> anyhow::Result<Vec<X25519Recipient>> {
+    if arg.starts_with("age1") {
+        return Ok(vec![arg.parse()?]);
+    }
+    let data = fs::read(arg)?;
+    if let Ok(text) = std::str::from_utf8(&data)
+        && text.lines().any(|line| line.trim().starts_with("age1"))
+    {
+        return key_lines(text).map(str::parse).collect();
+    }
+    let (key, _) = parse_ed25519_key(&data, KeyKind::Ed25519Public)?;
+    Ok(vec![X25519Recipient::from_ed25519(&key.verifying_key())])
+}
+
+/// Identities from an `age-keygen` style file or an Ed25519 secret key in
+/// any supported format.
+pub fn read_identities(path: impl AsRef<Path>) -> anyhow::Result<Vec<X25519Identity>> {
+    let path = path.as_ref();
+    let data = fs::read(path)?;
+    if let Ok(text) = std::str::from_utf8(&data)
+        && text.contains("AGE-SECRET-KEY-1")
+    {
+        return key_lines(text).map(str::parse).collect();
+    }
+    match parse_ed25519_key(&data, KeyKind::Ed25519Secret)?.0 {
+        Ed25519Key::Secret(key) => Ok(vec![X25519Identity::from_ed25519(&key)]),
+        Ed25519Key::Public(_) => anyhow::bail!(
+            "{} is a public key, opening needs the secret key",
+            path.display()
+        ),
+    }
+}
+
+fn key_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Encrypts `reader` to every recipient in the age v1 format, armored unless
+/// `armor` is false.
+pub fn seal(
+    recipients: &[X25519Recipient],
+    armor: bool,
+    reader: &mut dyn Read,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    if recipients.is_empty() {
+        anyhow::bail!("No recipients given");
+    }
+    let mut file_key = Zeroizing::new([0u8; FILE_KEY_LEN]);
+    OsRng.fill_bytes(&mut *file_key);
+
+    let mut header = format!("{}\n", INTRO);
+    for recipient in recipients {
+        header.push_str(&recipient.wrap(&file_key)?.to_text());
+    }
+    header.push_str("---");
+    let mac = header_mac(&file_key[..])
+        .chain_update(header.as_bytes())
+        .finalize()
+        .into_bytes();
+    header.push_str(&format!(
+        " {}\n",
+        base64_encode(Base64Format::StandardNoPad, &mac)
+    ));
+
+    let mut writer = BufWriter::new(writer);
+    if armor {
+        writeln!(writer, "{}", ARMOR_BEGIN)?;
+        let mut body = Base64Writer::new(writer, Base64Format::Standard, COLUMNS, false);
+        seal_payload(&file_key, &header, reader, &mut body)?;
+        writer = body.finish()?;
+        write!(writer, "\n{}\n", ARMOR_END)?;
+    } else {
+        seal_payload(&file_key, &header, reader, &mut writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn seal_payload(
+    file_key: &[u8; FILE_KEY_LEN],
+    header: &str,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    writer.write_all(header.as_bytes())?;
+    writer.write_all(&nonce)?;
+    let key = hkdf(&nonce, file_key, b"payload");
+    seal_chunks(Sealer::age(&key[..])?, CHUNK_SIZE, b"", reader, writer)
+}
+
+/// Decrypts an armored or binary age file with the first identity that
+/// matches one of its recipients.
+pub fn open(
+    identities: &[X25519Identity],
+    reader: impl Read,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+    if reader.fill_buf()?.starts_with(ARMOR_BEGIN.as_bytes()) {
+        let mut begin = String::new();
+        reader.read_line(&mut begin)?;
+        let body = Base64Reader::new(ArmorBody::new(reader, ARMOR_END), Base64Format::Standard);
+        open_payload(identities, &mut BufReader::new(body), &mut writer)?;
+    } else {
+        open_payload(identities, &mut reader, &mut writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn open_payload(
+    identities: &[X25519Identity],
+    reader: &mut dyn BufRead,
+    writer: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let (stanzas, mac_input, mac) = read_header(reader)?;
+    let mut file_key = None;
+    'identities: for identity in identities {
+        for stanza in &stanzas {
+            if let Some(key) = identity.unwrap(stanza)? {
+                file_key = Some(key);
+                break 'identities;
+            }
+        }
+    }
+    let file_key = file_key.ok_or_else(|| anyhow::anyhow!("No identity matches a recipient"))?;
+    header_mac(&file_key[..])
+        .chain_update(&mac_input)
+        .verify_slice(&mac)
+        .map_err(|_| anyhow::anyhow!("age header MAC mismatch, the header was altered"))?;
+
+    let mut nonce = [0u8; NONCE_LEN];
+    reader.read_exact(&mut nonce)?;
+    let key = hkdf(&nonce, &file_key[..], b"payload");
+    open_chunks(Opener::age(&key[..])?, CHUNK_SIZE, b"", reader, writer)
+}
+
+// Returns the stanzas, the header bytes the MAC covers and the MAC.
+fn read_header(reader: &mut dyn BufRead) -> anyhow::Result<(Vec<Stanza>, Vec<u8>, Vec<u8>)> {
+    let mut raw = Vec::new();
+    if read_header_line(reader, &mut raw)? != INTRO {
+        anyhow::bail!("Not an age v1 file");
+    }
+    let mut stanzas = Vec::new();
+    loop {
+        let line = read_header_line(reader, &mut raw)?;
+        if let Some(mac) = line.strip_prefix("--- ") {
+            // the MAC covers the header up to and including "---"
+            raw.truncate(raw.len() - line.len() - 1 + 3);
+            let mac = base64_decode(Base64Format::StandardNoPad, mac)?;
+            return Ok((stanzas, raw, mac));
+        }
+        let args: Vec<String> = match line.strip_prefix("-> ") {
+            Some(args) => args.split(' ').map(str::to_string).collect(),
+            None => anyhow::bail!("Invalid age header line: {}", line),
+        };
+        if args.iter().any(|arg| arg.is_empty()) {
+            anyhow::bail!("Invalid age stanza: {}", line);
+        }
+        // the body ends with the first line shorter than a full one
+        let mut body = Vec::new();
+        loop {
+            let line = read_header_line(reader, &mut raw)?;
+            if line.len() > COLUMNS {
+                anyhow::bail!("age stanza line is too long");
+            }
+            body.extend(base64_decode(Base64Format::StandardNoPad, &line)?);
+            if line.len() < COLUMNS {
+                break;
+            }
+        }
+        stanzas.push(Stanza { args, body });
+        if stanzas.len() > MAX_STANZAS {
+            anyhow::bail!("age header has too many stanzas");
+        }
+    }
+}
+
+fn read_header_line(reader: &mut dyn BufRead, raw: &mut Vec<u8>) -> anyhow::Result<String> {
+    let mut line = Vec::new();
+    Read::take(&mut *reader, MAX_HEADER_LINE).read_until(b'\n', &mut line)?;
+    if line.pop() != Some(b'\n') {
+        anyhow::bail!("Truncated age header");
+    }
+    raw.extend_from_slice(&line);
+    raw.push(b'\n');
+    Ok(String::from_utf8(line)?)
+}
+
+impl Stanza {
+    fn to_text(&self) -> String {
+        let body = base64_encode(Base64Format::StandardNoPad, &self.body);
+        let mut text = format!("-> {}\n", self.args.join(" "));
+        let mut lines = body.as_bytes().chunks(COLUMNS).peekable();
+        while let Some(line) = lines.next() {
+            text.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+            text.push('\n');
+            // a full last line needs an empty one after it to end the body
+            if lines.peek().is_none() && line.len() == COLUMNS {
+                text.push('\n');
+            }
+        }
+        if body.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn header_mac(file_key: &[u8]) -> Hmac<Sha256> {
+    let key = hkdf(&[], file_key, b"header");
+    <Hmac<Sha256> as Mac>::new_from_slice(&key[..]).expect("HMAC takes any key length")
+}
+
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut okm = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut *okm)
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    okm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> X25519Identity {
+        X25519Identity::from_bytes([7; 32])
+    }
+
+    fn seal_to(recipients: &[X25519Recipient], data: &[u8], armor: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        seal(recipients, armor, &mut &data[..], &mut out).expect("seal");
+        out
+    }
+
+    fn open_with(identity: &X25519Identity, sealed: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        open(std::slice::from_ref(identity), sealed, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_age_key_encoding() -> anyhow::Result<()> {
+        let identity = identity();
+        let encoded = identity.to_string();
+        assert!(encoded.starts_with("AGE-SECRET-KEY-1"));
+        let recipient = identity.recipient();
+        assert!(recipient.to_string().starts_with("age1"));
+        assert_eq!(encoded.parse::<X25519Identity>()?.recipient(), recipient);
+        assert_eq!(recipient.to_string().parse::<X25519Recipient>()?, recipient);
+        // Bech32 checksums catch typos
+        let mut typo = recipient.to_string();
+        typo.replace_range(10..11, if &typo[10..11] == "q" { "p" } else { "q" });
+        assert!(typo.parse::<X25519Recipient>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_open_round_trip() -> anyhow::Result<()> {
+        let other = X25519Identity::from_bytes([9; 32]);
+        let recipients = [identity().recipient(), other.recipient()];
+        for armor in [true, false] {
+            for len in [0, 1, CHUNK_SIZE, CHUNK_SIZE + 1] {
+                let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+                let sealed = seal_to(&recipients, &data, armor);
+                assert_eq!(sealed.starts_with(ARMOR_BEGIN.as_bytes()), armor);
+                assert_eq!(open_with(&identity(), &sealed)?, data);
+                assert_eq!(open_with(&other, &sealed)?, data);
+            }
+        }
+        let stranger = X25519Identity::from_bytes([1; 32]);
+        let sealed = seal_to(&recipients[..1], b"secret", true);
+        let err = open_with(&stranger, &sealed).unwrap_err();
+        assert!(err.to_string().contains("No identity matches"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_rejects_altered_header() -> anyhow::Result<()> {
+        let sealed = seal_to(&[identity().recipient()], b"secret", false);
+        // an extra stanza still parses, but the header MAC no longer matches
+        let at = sealed
+            .windows(4)
+            .position(|w| w == b"\n---")
+            .expect("MAC line")
+            + 1;
+        let mut altered = sealed.clone();
+        altered.splice(at..at, b"-> grease\n\n".iter().copied());
+        let err = open_with(&identity(), &altered).unwrap_err();
+        assert!(err.to_string().contains("MAC mismatch"));
+        assert!(open_with(&identity(), &sealed[..sealed.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_file_from_age() -> anyhow::Result<()> {
+        // encrypted by the reference implementation, with a grease stanza
+        let identities = read_identities("fixtures/age.key")?;
+        let mut out = Vec::new();
+        open(&identities, fs::File::open("fixtures/hello.age")?, &mut out)?;
+        assert_eq!(out, b"hello from age\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed25519_conversion() -> anyhow::Result<()> {
+        let identities = read_identities("fixtures/ed25519.sk")?;
+        let recipients = read_recipients("fixtures/ed25519.pk")?;
+        assert_eq!(identities[0].recipient(), recipients[0]);
+        let sealed = seal_to(&recipients, b"to an ssh key", true);
+        assert_eq!(open_with(&identities[0], &sealed)?, b"to an ssh key");
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_last_chunk_only_for_empty_payload() -> anyhow::Result<()> {
+        // age's payload nonce: 11 byte big endian counter and a last flag
+        let key = [9u8; 32];
+        let cipher = ChaCha20Poly1305::new((&key).into());
+        let chunk = |counter: u8, last: bool, data: &[u8]| {
+            let nonce = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, counter, last as u8];
+            cipher
+                .encrypt((&nonce).into(), data)
+                .expect("encrypting to a Vec can't fail")
+        };
+        let open = |payload: Vec<u8>| -> anyhow::Result<Vec<u8>> {
+            let mut out = Vec::new();
+            open_chunks(
+                Opener::age(&key)?,
+                CHUNK_SIZE,
+                b"",
+                &mut payload.as_slice(),
+                &mut out,
+            )?;
+            Ok(out)
+        };
+
+        let full = vec![7u8; CHUNK_SIZE];
+        assert!(open(chunk(0, true, b""))?.is_empty());
+        assert_eq!(open(chunk(0, true, &full))?, full);
+        let trailing_empty = [chunk(0, false, &full), chunk(1, true, b"")].concat();
+        assert!(open(trailing_empty).is_err());
+        Ok(())
+    }
+}
@@ -1,37 +1,268 @@
-use std::io::Read;
+use std::io::{self, BufWriter, Read, Write};
 
 use base64::{
-    Engine as _,
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine as _, alphabet,
+    engine::{
+        DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig,
+        general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+    },
+    read::DecoderReader,
+    write::EncoderWriter,
 };
 
-use crate::{Base64DecodeOpts, Base64EncodeOpts, Base64Format, get_reader};
+use crate::{
+    Base64DecodeOpts, Base64EncodeOpts, Base64Format, Compressor, decompress_reader, get_reader,
+};
 
-pub fn process_base64_encode(opts: Base64EncodeOpts) -> anyhow::Result<String> {
-    let mut reader: Box<dyn Read> = get_reader(&opts.input)?;
+// Lenient input is mapped onto the standard alphabet before decoding, so a
+// single engine that doesn't care about padding covers every variant.
+const LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
 
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
+/// Streams `opts.input` into `writer` as base64, optionally compressing it
+/// first, ending with a newline.
+pub fn process_base64_encode<W: Write>(opts: Base64EncodeOpts, writer: W) -> anyhow::Result<()> {
+    let mut reader = get_reader(&opts.input)?;
+    let mut encoder = Base64Writer::new(BufWriter::new(writer), opts.format, opts.wrap, opts.crlf);
+    if let Some(format) = opts.compress {
+        let mut compressor = Compressor::new(encoder, format, None)?;
+        io::copy(&mut reader, &mut compressor)?;
+        encoder = compressor.finish()?;
+    } else {
+        io::copy(&mut reader, &mut encoder)?;
+    }
+    let mut writer = encoder.finish()?;
+    writer.write_all(if opts.crlf { b"\r\n" } else { b"\n" })?;
+    writer.flush()?;
+    Ok(())
+}
 
-    let encoded = match opts.format {
-        Base64Format::Standard => STANDARD.encode(&buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(&buf),
+/// Streams the decoded bytes of `opts.input` into `writer` and returns the
+/// variant that was used, which is only different from `opts.format` in
+/// lenient mode.
+pub fn process_base64_decode<W: Write>(
+    opts: Base64DecodeOpts,
+    writer: W,
+) -> anyhow::Result<Base64Format> {
+    let reader = get_reader(&opts.input)?;
+    let mut decoder = if opts.lenient {
+        Base64Reader::lenient(reader)
+    } else {
+        Base64Reader::new(reader, opts.format)
     };
-    Ok(encoded)
+    let mut writer = BufWriter::new(writer);
+    match opts.decompress {
+        Some(format) => io::copy(&mut decompress_reader(format, &mut decoder)?, &mut writer)?,
+        None => io::copy(&mut decoder, &mut writer)?,
+    };
+    writer.flush()?;
+    Ok(decoder.into_format())
+}
+
+pub fn base64_encode(format: Base64Format, data: &[u8]) -> String {
+    engine(format).encode(data)
+}
+
+pub fn base64_decode(format: Base64Format, data: &str) -> anyhow::Result<Vec<u8>> {
+    Ok(engine(format).decode(data)?)
+}
+
+/// Ignores all whitespace, picks the alphabet from the characters in use and
+/// accepts input with or without padding.
+pub fn base64_decode_lenient(data: &str) -> anyhow::Result<(Vec<u8>, Base64Format)> {
+    let mut decoder = Base64Reader::lenient(data.as_bytes());
+    let mut decoded = Vec::new();
+    decoder.read_to_end(&mut decoded)?;
+    Ok((decoded, decoder.into_format()))
+}
+
+fn engine(format: Base64Format) -> &'static GeneralPurpose {
+    match format {
+        Base64Format::Standard => &STANDARD,
+        Base64Format::StandardNoPad => &STANDARD_NO_PAD,
+        Base64Format::UrlSafe => &URL_SAFE_NO_PAD,
+        Base64Format::UrlSafePad => &URL_SAFE,
+    }
+}
+
+/// `Write` adapter that base64 encodes everything written to it and passes
+/// it on to `W` in lines of `wrap` columns (0 for a single line). `finish`
+/// must be called to write the last partial block.
+pub struct Base64Writer<W: Write> {
+    inner: EncoderWriter<'static, GeneralPurpose, LineWrapper<W>>,
+}
+
+impl<W: Write> Base64Writer<W> {
+    pub fn new(writer: W, format: Base64Format, wrap: usize, crlf: bool) -> Self {
+        let wrapper = LineWrapper {
+            inner: writer,
+            width: wrap,
+            eol: if crlf { b"\r\n" } else { b"\n" },
+            column: 0,
+        };
+        Self {
+            inner: EncoderWriter::new(wrapper, engine(format)),
+        }
+    }
+
+    /// Writes the final block and padding and returns the wrapped writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        Ok(self.inner.finish()?.inner)
+    }
+}
+
+impl<W: Write> Write for Base64Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Line breaks go in front of the next character, so output that exactly
+// fills its last line doesn't end with a dangling break.
+struct LineWrapper<W: Write> {
+    inner: W,
+    width: usize,
+    eol: &'static [u8],
+    column: usize,
+}
+
+impl<W: Write> Write for LineWrapper<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.width == 0 {
+            return self.inner.write(buf);
+        }
+        let mut rest = buf;
+        while !rest.is_empty() {
+            if self.column == self.width {
+                self.inner.write_all(self.eol)?;
+                self.column = 0;
+            }
+            let (line, tail) = rest.split_at(rest.len().min(self.width - self.column));
+            self.inner.write_all(line)?;
+            self.column += line.len();
+            rest = tail;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `Read` adapter that decodes base64 from `R` as it is read. Whitespace and
+/// line breaks are always skipped; the lenient reader also accepts either
+/// alphabet and optional padding.
+pub struct Base64Reader<R: Read> {
+    inner: DecoderReader<'static, GeneralPurpose, Base64Filter<R>>,
+    format: Option<Base64Format>,
 }
 
-pub fn process_base64_decode(opts: Base64DecodeOpts) -> anyhow::Result<Vec<u8>> {
-    let mut reader: Box<dyn Read> = get_reader(&opts.input)?;
+impl<R: Read> Base64Reader<R> {
+    pub fn new(reader: R, format: Base64Format) -> Self {
+        Self {
+            inner: DecoderReader::new(Base64Filter::new(reader, false), engine(format)),
+            format: Some(format),
+        }
+    }
 
-    let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
-    let buf = buf.trim();
+    pub fn lenient(reader: R) -> Self {
+        Self {
+            inner: DecoderReader::new(Base64Filter::new(reader, true), &LENIENT),
+            format: None,
+        }
+    }
+
+    /// The variant that was decoded; for a lenient reader this is what was
+    /// detected in the input read so far.
+    pub fn into_format(self) -> Base64Format {
+        if let Some(format) = self.format {
+            return format;
+        }
+        let filter = self.inner.into_inner();
+        match (filter.url_safe == Some(true), filter.padded) {
+            (true, true) => Base64Format::UrlSafePad,
+            (true, false) => Base64Format::UrlSafe,
+            (false, true) => Base64Format::Standard,
+            (false, false) => Base64Format::StandardNoPad,
+        }
+    }
+}
+
+impl<R: Read> Read for Base64Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
 
-    let decoded = match opts.format {
-        Base64Format::Standard => STANDARD.decode(buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(buf),
-    }?;
-    Ok(decoded)
+// Drops whitespace and, when lenient, maps the URL-safe alphabet onto the
+// standard one while remembering which was used.
+struct Base64Filter<R: Read> {
+    inner: R,
+    lenient: bool,
+    url_safe: Option<bool>,
+    padded: bool,
+}
+
+impl<R: Read> Base64Filter<R> {
+    fn new(inner: R, lenient: bool) -> Self {
+        Self {
+            inner,
+            lenient,
+            url_safe: None,
+            padded: false,
+        }
+    }
+}
+
+impl<R: Read> Read for Base64Filter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.inner.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut len = 0;
+            for i in 0..n {
+                let mut c = buf[i];
+                if c.is_ascii_whitespace() {
+                    continue;
+                }
+                if self.lenient {
+                    let url_safe = match c {
+                        b'-' | b'_' => Some(true),
+                        b'+' | b'/' => Some(false),
+                        _ => None,
+                    };
+                    if let Some(url_safe) = url_safe
+                        && self.url_safe.replace(url_safe) == Some(!url_safe)
+                    {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Input mixes the standard and URL-safe base64 alphabets",
+                        ));
+                    }
+                    c = match c {
+                        b'-' => b'+',
+                        b'_' => b'/',
+                        c => c,
+                    };
+                }
+                self.padded = c == b'=';
+                buf[len] = c;
+                len += 1;
+            }
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -42,9 +273,13 @@ mod tests {
     fn test_base64_encode() {
         let opts = Base64EncodeOpts {
             input: "Cargo.toml".to_string(),
+            output: None,
             format: Base64Format::Standard,
+            wrap: 0,
+            crlf: false,
+            compress: None,
         };
-        let result = process_base64_encode(opts);
+        let result = process_base64_encode(opts, io::sink());
         assert!(result.is_ok());
     }
 
@@ -52,9 +287,139 @@ mod tests {
     fn test_base64_decode() {
         let opts = Base64DecodeOpts {
             input: "fixtures/temp.b64".to_string(),
+            output: None,
             format: Base64Format::UrlSafe,
+            lenient: false,
+            decompress: None,
         };
-        let result = process_base64_decode(opts);
+        let result = process_base64_decode(opts, io::sink());
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_base64_binary_round_trip() -> anyhow::Result<()> {
+        let key = std::fs::read("fixtures/ed25519.sk")?;
+        let mut encoded = Vec::new();
+        process_base64_encode(
+            Base64EncodeOpts {
+                input: "fixtures/ed25519.sk".to_string(),
+                output: None,
+                format: Base64Format::UrlSafe,
+                wrap: 0,
+                crlf: false,
+                compress: None,
+            },
+            &mut encoded,
+        )?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("ed25519.b64");
+        std::fs::write(&path, encoded)?;
+        let mut decoded = Vec::new();
+        process_base64_decode(
+            Base64DecodeOpts {
+                input: path.to_string_lossy().into_owned(),
+                output: None,
+                format: Base64Format::UrlSafe,
+                lenient: false,
+                decompress: None,
+            },
+            &mut decoded,
+        )?;
+        assert_eq!(decoded, key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_variants() -> anyhow::Result<()> {
+        let data = b"\xfb\xff\xbfrcli";
+        for (format, expected) in [
+            (Base64Format::Standard, "+/+/cmNsaQ=="),
+            (Base64Format::StandardNoPad, "+/+/cmNsaQ"),
+            (Base64Format::UrlSafe, "-_-_cmNsaQ"),
+            (Base64Format::UrlSafePad, "-_-_cmNsaQ=="),
+        ] {
+            assert_eq!(base64_encode(format, data), expected);
+            assert_eq!(base64_decode(format, expected)?, data);
+            assert_eq!(base64_decode_lenient(expected)?, (data.to_vec(), format));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_lenient_decode() -> anyhow::Result<()> {
+        let mut encoder = Base64Writer::new(Vec::new(), Base64Format::Standard, 76, true);
+        encoder.write_all(&[7u8; 100])?;
+        let wrapped = String::from_utf8(encoder.finish()?)?;
+        assert!(wrapped.contains("\r\n"));
+        assert_eq!(wrapped.lines().next().map(str::len), Some(76));
+        assert_eq!(base64_decode_lenient(&wrapped)?.0, [7u8; 100]);
+        // missing padding and stray whitespace
+        assert_eq!(base64_decode_lenient(" aGVs\nbG8 ")?.0, b"hello");
+        assert!(base64_decode_lenient("ab+c-d").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_streaming() -> anyhow::Result<()> {
+        // larger than the adapters' internal buffers, written in odd sized chunks
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut encoder = Base64Writer::new(Vec::new(), Base64Format::UrlSafe, 64, false);
+        for chunk in data.chunks(1000 - 1) {
+            encoder.write_all(chunk)?;
+        }
+        let encoded = encoder.finish()?;
+        assert!(encoded.split(|&c| c == b'\n').all(|line| line.len() <= 64));
+        assert!(!encoded.ends_with(b"\n"));
+        assert_eq!(
+            String::from_utf8(encoded.clone())?.replace('\n', ""),
+            base64_encode(Base64Format::UrlSafe, &data)
+        );
+
+        let mut decoded = Vec::new();
+        Base64Reader::new(&encoded[..], Base64Format::UrlSafe).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, data);
+
+        let mut decoder = Base64Reader::lenient(&encoded[..]);
+        decoded.clear();
+        decoder.read_to_end(&mut decoded)?;
+        assert_eq!(decoded, data);
+        assert_eq!(decoder.into_format(), Base64Format::UrlSafe);
+
+        let mut strict = Base64Reader::new(&encoded[..], Base64Format::Standard);
+        assert!(strict.read_to_end(&mut Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_compressed_round_trip() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cargo_lock.b64");
+        process_base64_encode(
+            Base64EncodeOpts {
+                input: "Cargo.lock".to_string(),
+                output: None,
+                format: Base64Format::Standard,
+                wrap: 76,
+                crlf: false,
+                compress: Some(crate::CompressFormat::Brotli),
+            },
+            std::fs::File::create(&path)?,
+        )?;
+        let original = std::fs::read("Cargo.lock")?;
+        assert!(std::fs::metadata(&path)?.len() < original.len() as u64 / 2);
+
+        let mut decoded = Vec::new();
+        process_base64_decode(
+            Base64DecodeOpts {
+                input: path.to_string_lossy().into_owned(),
+                output: None,
+                format: Base64Format::Standard,
+                lenient: false,
+                decompress: Some(crate::CompressFormat::Brotli),
+            },
+            &mut decoded,
+        )?;
+        assert_eq!(decoded, original);
+        Ok(())
+    }
 }
@@ -0,0 +1,97 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use sha1::{Digest, Sha1};
+
+/// Offline lookup in a Have-I-Been-Pwned "ordered by hash" SHA-1 dump.
+///
+/// Every line of the dump looks like `<SHA1 HEX>:<COUNT>` and lines are
+/// sorted by hash, so lookups binary search the file by byte offset and
+/// never load it into memory.
+pub struct BreachDb {
+    reader: BufReader<File>,
+    len: u64,
+}
+
+impl BreachDb {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            reader: BufReader::new(file),
+            len,
+        })
+    }
+
+    /// Returns how often the password appears in the dump, 0 if it doesn't.
+    pub fn lookup(&mut self, password: &str) -> anyhow::Result<u64> {
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        self.lookup_hash(&hash)
+    }
+
+    pub fn lookup_hash(&mut self, hash: &str) -> anyhow::Result<u64> {
+        let target = hash.to_ascii_uppercase();
+        // invariant: if the target line exists, it starts in [lo, hi)
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let (start, line) = self.line_from(mid)?;
+            if start >= hi || line.is_empty() {
+                hi = mid;
+                continue;
+            }
+            let entry = line.trim_end();
+            let (entry_hash, count) = entry.split_once(':').unwrap_or((entry, "1"));
+            match entry_hash.to_ascii_uppercase().as_str().cmp(&target) {
+                Ordering::Equal => return Ok(count.trim().parse()?),
+                Ordering::Less => lo = start + line.len() as u64,
+                Ordering::Greater => hi = mid,
+            }
+        }
+        Ok(0)
+    }
+
+    // Reads the first complete line starting at or after `offset`.
+    fn line_from(&mut self, offset: u64) -> anyhow::Result<(u64, String)> {
+        let mut start = offset;
+        if offset > 0 {
+            self.reader.seek(SeekFrom::Start(offset - 1))?;
+            let mut skipped = Vec::new();
+            let n = self.reader.read_until(b'\n', &mut skipped)?;
+            start = offset - 1 + n as u64;
+        } else {
+            self.reader.seek(SeekFrom::Start(0))?;
+        }
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        Ok((start, line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breach_db_lookup() -> anyhow::Result<()> {
+        let mut db = BreachDb::open("fixtures/pwned-passwords-sha1.txt")?;
+        assert_eq!(db.lookup("password")?, 10434004);
+        assert_eq!(db.lookup("123456")?, 46980922);
+        assert_eq!(db.lookup("qwerty")?, 13486072);
+        assert_eq!(db.lookup("correct horse battery staple, but longer")?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_breach_db_every_entry() -> anyhow::Result<()> {
+        let content = std::fs::read_to_string("fixtures/pwned-passwords-sha1.txt")?;
+        let mut db = BreachDb::open("fixtures/pwned-passwords-sha1.txt")?;
+        for line in content.lines() {
+            let (hash, count) = line.split_once(':').unwrap();
+            assert_eq!(db.lookup_hash(hash)?, count.parse::<u64>()?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,706 @@
+use std::io::{BufWriter, Read, Write};
+
+use anyhow::Context;
+use data_encoding::{BASE32, Encoding, HEXLOWER, HEXLOWER_PERMISSIVE, HEXUPPER, Specification};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, percent_encode};
+
+use crate::{
+    Base64Format, CodecFormat, CompressFormat, DecodeOpts, EncodeOpts, base64_decode,
+    base64_decode_lenient, base64_encode, decompress_reader, detect_compression, get_reader,
+};
+
+use super::encrypt::read_full;
+
+const RFC1924_CHARS: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+const Z85_CHARS: &[u8; 85] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+// nested encodings deeper than this are more likely a detection loop
+const MAX_LAYERS: usize = 16;
+// a few KiB of base64 can hide a gzip bomb of gigabytes
+const MAX_DECOMPRESSED: u64 = 64 * 1024 * 1024;
+// groups per streamed chunk, 48 KiB of input for base64
+const CHUNK_GROUPS: usize = 16 * 1024;
+// RFC 3986 unreserved characters stay as they are
+const PERCENT_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+pub trait Codec {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<String>;
+    fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>>;
+
+    /// Bytes and encoded characters per group. Whole groups encode and
+    /// decode on their own, so a stream can be cut at any multiple of them.
+    /// `None` for codecs that need all of their input at once.
+    fn group(&self) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+/// Looks up the codec implementing `format`.
+pub fn codec(format: CodecFormat) -> Box<dyn Codec> {
+    match format {
+        CodecFormat::Hex => Box::new(Hex { upper: false }),
+        CodecFormat::HexUpper => Box::new(Hex { upper: true }),
+        CodecFormat::Base32 => Box::new(DataEncoding(BASE32)),
+        CodecFormat::Base32Crockford => Box::new(DataEncoding(crockford())),
+        CodecFormat::Base58 => Box::new(Base58 { check: false }),
+        CodecFormat::Base58Check => Box::new(Base58 { check: true }),
+        CodecFormat::Base64 => Box::new(Base64(Base64Format::Standard)),
+        CodecFormat::Base64Url => Box::new(Base64(Base64Format::UrlSafe)),
+        CodecFormat::Base85 => Box::new(Base85::rfc1924()),
+        CodecFormat::Ascii85 => Box::new(Base85::ascii85()),
+        CodecFormat::Z85 => Box::new(Base85::z85()),
+        CodecFormat::Percent => Box::new(Percent),
+    }
+}
+
+/// Streams `opts.input` into `writer` in the chosen encoding, ending with a
+/// newline. Base58, Ascii85 and percent-encoding read all of the input
+/// first: base58 is one big number, the other two have no fixed groups.
+pub fn process_encode<W: Write>(opts: EncodeOpts, writer: W) -> anyhow::Result<()> {
+    let mut reader = get_reader(&opts.input)?;
+    let codec = codec(opts.codec);
+    let mut writer = BufWriter::new(writer);
+    match codec.group() {
+        Some((bytes, _)) => {
+            let mut buf = vec![0u8; bytes * CHUNK_GROUPS];
+            loop {
+                let n = read_full(&mut reader, &mut buf)?;
+                writer.write_all(codec.encode(&buf[..n])?.as_bytes())?;
+                if n < buf.len() {
+                    break;
+                }
+            }
+        }
+        None => {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+            writer.write_all(codec.encode(&buf)?.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Decodes with the given codec into `writer`, streaming the same codecs
+/// `process_encode` does. `--auto` needs all of the input to detect layers
+/// and returns the names of the layers that were removed.
+pub fn process_decode<W: Write>(opts: DecodeOpts, writer: W) -> anyhow::Result<Vec<String>> {
+    let mut reader = get_reader(&opts.input)?;
+    let mut writer = BufWriter::new(writer);
+    let layers = match opts.codec.map(codec) {
+        Some(codec) => {
+            match codec.group() {
+                Some((_, chars)) => decode_stream(&*codec, chars, &mut reader, &mut writer)?,
+                None => {
+                    let mut buf = Vec::new();
+                    reader.read_to_end(&mut buf)?;
+                    let text = String::from_utf8(buf).context("Encoded input must be text")?;
+                    writer.write_all(&codec.decode(text.trim())?)?;
+                }
+            }
+            Vec::new()
+        }
+        None => {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+            let (data, layers) = peel_layers(buf)?;
+            writer.write_all(&data)?;
+            layers
+        }
+    };
+    writer.flush()?;
+    Ok(layers)
+}
+
+// Whitespace is dropped on the way, so every chunk but the last one holds
+// whole groups.
+fn decode_stream(
+    codec: &dyn Codec,
+    chars: usize,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let chunk_len = chars * CHUNK_GROUPS;
+    let mut buf = vec![0u8; chunk_len];
+    let mut text = Vec::with_capacity(chunk_len * 2);
+    loop {
+        let n = read_full(reader, &mut buf)?;
+        text.extend(buf[..n].iter().filter(|c| !c.is_ascii_whitespace()));
+        let end = if n < buf.len() {
+            text.len()
+        } else {
+            text.len() - text.len() % chunk_len
+        };
+        let chunk = std::str::from_utf8(&text[..end]).context("Encoded input must be text")?;
+        writer.write_all(&codec.decode(chunk)?)?;
+        text.drain(..end);
+        if n < buf.len() {
+            return Ok(());
+        }
+    }
+}
+
+/// Repeatedly detects the outermost encoding or compression and removes it,
+/// stopping at JSON, plain text or binary, which ends the returned chain.
+pub fn peel_layers(mut data: Vec<u8>) -> anyhow::Result<(Vec<u8>, Vec<String>)> {
+    let mut layers = Vec::new();
+    for _ in 0..MAX_LAYERS {
+        match peel_layer(&data)? {
+            Some((layer, inner)) => {
+                layers.push(layer);
+                data = inner;
+            }
+            None => {
+                layers.push(content_kind(&data).to_string());
+                return Ok((data, layers));
+            }
+        }
+    }
+    anyhow::bail!(
+        "Gave up after {} layers: {}",
+        MAX_LAYERS,
+        layers.join(" -> ")
+    )
+}
+
+fn peel_layer(data: &[u8]) -> anyhow::Result<Option<(String, Vec<u8>)>> {
+    if let Some((format, inner)) = decompress_magic(data)? {
+        return Ok(Some((format.to_string(), inner)));
+    }
+    let Ok(text) = std::str::from_utf8(data).map(str::trim) else {
+        return Ok(None);
+    };
+    if text.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(serde_json::Value::String(inner)) = serde_json::from_str(text) {
+        return Ok(Some(("json string".to_string(), inner.into_bytes())));
+    }
+    if looks_like_hex(text)
+        && let Ok(inner) = codec(CodecFormat::Hex).decode(text)
+        && is_recognizable(&inner)
+    {
+        return Ok(Some(("hex".to_string(), inner)));
+    }
+    if looks_like_percent(text)
+        && let Ok(inner) = codec(CodecFormat::Percent).decode(text)
+    {
+        return Ok(Some(("percent".to_string(), inner)));
+    }
+    if looks_like_base64(text)
+        && let Ok((inner, format)) = base64_decode_lenient(text)
+        && is_recognizable(&inner)
+    {
+        return Ok(Some((format!("base64 ({})", format), inner)));
+    }
+    Ok(None)
+}
+
+fn decompress_magic(data: &[u8]) -> anyhow::Result<Option<(CompressFormat, Vec<u8>)>> {
+    let Some(format) = detect_compression(data) else {
+        return Ok(None);
+    };
+    let mut inner = Vec::new();
+    let decompressed = decompress_reader(format, data)
+        .and_then(|reader| reader.take(MAX_DECOMPRESSED + 1).read_to_end(&mut inner));
+    if decompressed.is_err() {
+        return Ok(None);
+    }
+    if inner.len() as u64 > MAX_DECOMPRESSED {
+        anyhow::bail!(
+            "{} layer decompresses to more than {} MiB",
+            format,
+            MAX_DECOMPRESSED / 1024 / 1024
+        );
+    }
+    Ok(Some((format, inner)))
+}
+
+fn content_kind(data: &[u8]) -> &'static str {
+    match std::str::from_utf8(data) {
+        Ok(text) if serde_json::from_str::<serde_json::Value>(text).is_ok() => "json",
+        Ok(_) => "text",
+        Err(_) => "binary",
+    }
+}
+
+// Decoding only pays off if it leads somewhere we know: text, which may hold
+// the next layer, or compressed data. Otherwise tokens like "password" or
+// "12345678" would be taken for base64 or hex of random bytes.
+fn is_recognizable(data: &[u8]) -> bool {
+    let compressed = detect_compression(data).is_some_and(|format| {
+        // a peek is enough to tell, the real decompression is capped
+        decompress_reader(format, data)
+            .and_then(|mut reader| reader.read(&mut [0u8; 64]))
+            .is_ok()
+    });
+    compressed
+        || std::str::from_utf8(data)
+            .is_ok_and(|text| !text.chars().any(|c| c.is_control() && !c.is_whitespace()))
+}
+
+fn looks_like_hex(text: &str) -> bool {
+    let digits = strip_whitespace(text);
+    digits.len() >= 8
+        && digits.len().is_multiple_of(2)
+        && digits.bytes().all(|c| c.is_ascii_hexdigit())
+}
+
+fn looks_like_percent(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes
+        .windows(3)
+        .any(|w| w[0] == b'%' && w[1].is_ascii_hexdigit() && w[2].is_ascii_hexdigit())
+}
+
+fn looks_like_base64(text: &str) -> bool {
+    let chars = strip_whitespace(text);
+    chars.len() >= 4
+        && chars.len() % 4 != 1
+        && chars
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"+/-_=".contains(&c))
+}
+
+fn strip_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+struct Hex {
+    upper: bool,
+}
+
+impl Codec for Hex {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<String> {
+        let encoding = if self.upper { &HEXUPPER } else { &HEXLOWER };
+        Ok(encoding.encode(data))
+    }
+
+    fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(HEXLOWER_PERMISSIVE.decode(strip_whitespace(text).as_bytes())?)
+    }
+
+    fn group(&self) -> Option<(usize, usize)> {
+        Some((1, 2))
+    }
+}
+
+struct DataEncoding(Encoding);
+
+impl Codec for DataEncoding {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<String> {
+        Ok(self.0.encode(data))
+    }
+
+    fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(self.0.decode(strip_whitespace(text).as_bytes())?)
+    }
+
+    fn group(&self) -> Option<(usize, usize)> {
+        // both base32 alphabets encode 5 bytes as 8 characters
+        Some((5, 8))
+    }
+}
+
+// Crockford's base32: no I, L, O or U, decoding is case insensitive and
+// maps the look-alikes back.
+fn crockford() -> Encoding {
+    let mut spec = Specification::new();
+    spec.symbols.push_str("0123456789ABCDEFGHJKMNPQRSTVWXYZ");
+    spec.translate.from.push_str("abcdefghjkmnpqrstvwxyzoOiIlL");
+    spec.translate.to.push_str("ABCDEFGHJKMNPQRSTVWXYZ001111");
+    spec.encoding().expect("crockford specification is valid")
+}
+
+struct Base58 {
+    check: bool,
+}
+
+impl Codec for Base58 {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<String> {
+        let encoder = bs58::encode(data);
+        Ok(if self.check {
+            encoder.with_check().into_string()
+        } else {
+            encoder.into_string()
+        })
+    }
+
+    fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        let text = strip_whitespace(text);
+        let decoder = bs58::decode(&text);
+        Ok(if self.check {
+            decoder.with_check(None).into_vec()?
+        } else {
+            decoder.into_vec()?
+        })
+    }
+}
+
+struct Base64(Base64Format);
+
+impl Codec for Base64 {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<String> {
+        Ok(base64_encode(self.0, data))
+    }
+
+    fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        base64_decode(self.0, &strip_whitespace(text))
+    }
+
+    fn group(&self) -> Option<(usize, usize)> {
+        Some((3, 4))
+    }
+}
+
+/// The base85 family only differs in alphabet and a few extras: Ascii85
+/// abbreviates zero groups as `z` and is wrapped in `<~ ~>`, Z85 only works
+/// on whole 4 byte groups.
+struct Base85 {
+    chars: [u8; 85],
+    zero_group: bool,
+    delimiters: bool,
+    partial: bool,
+}
+
+impl Base85 {
+    fn rfc1924() -> Self {
+        Self {
+            chars: *RFC1924_CHARS,
+            zero_group: false,
+            delimiters: false,
+            partial: true,
+        }
+    }
+
+    fn ascii85() -> Self {
+        let mut chars = [0u8; 85];
+        for (i, c) in chars.iter_mut().enumerate() {
+            *c = b'!' + i as u8;
+        }
+        Self {
+            chars,
+            zero_group: true,
+            delimiters: true,
+            partial: true,
+        }
+    }
+
+    fn z85() -> Self {
+        Self {
+            chars: *Z85_CHARS,
+            zero_group: false,
+            delimiters: false,
+            partial: false,
+        }
+    }
+}
+
+impl Codec for Base85 {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<String> {
+        if !self.partial && !data.len().is_multiple_of(4) {
+            anyhow::bail!("Z85 input length must be a multiple of 4");
+        }
+        let mut out = String::with_capacity(data.len() * 5 / 4 + 5);
+        if self.delimiters {
+            out.push_str("<~");
+        }
+        for chunk in data.chunks(4) {
+            let mut group = [0u8; 4];
+            group[..chunk.len()].copy_from_slice(chunk);
+            let mut value = u32::from_be_bytes(group);
+            if self.zero_group && chunk.len() == 4 && value == 0 {
+                out.push('z');
+                continue;
+            }
+            let mut digits = [0u8; 5];
+            for digit in digits.iter_mut().rev() {
+                *digit = self.chars[(value % 85) as usize];
+                value /= 85;
+            }
+            // a partial group of n bytes only needs n + 1 digits
+            out.extend(digits[..chunk.len() + 1].iter().map(|&c| c as char));
+        }
+        if self.delimiters {
+            out.push_str("~>");
+        }
+        Ok(out)
+    }
+
+    fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        let mut text = strip_whitespace(text);
+        if self.delimiters {
+            text = text
+                .strip_prefix("<~")
+                .unwrap_or(&text)
+                .trim_end_matches("~>")
+                .to_string();
+        }
+
+        let mut out = Vec::with_capacity(text.len() * 4 / 5 + 4);
+        let mut group = Vec::with_capacity(5);
+        let flush = |group: &[u8], out: &mut Vec<u8>| -> anyhow::Result<()> {
+            let mut value: u32 = 0;
+            for i in 0..5 {
+                // missing digits of a partial group are padded with the highest digit
+                let digit = group.get(i).copied().unwrap_or(84) as u32;
+                value = value
+                    .checked_mul(85)
+                    .and_then(|v| v.checked_add(digit))
+                    .ok_or_else(|| anyhow::anyhow!("Invalid base85 group"))?;
+            }
+            out.extend_from_slice(&value.to_be_bytes()[..group.len() - 1]);
+            Ok(())
+        };
+        for c in text.bytes() {
+            if self.zero_group && c == b'z' {
+                if !group.is_empty() {
+                    anyhow::bail!("'z' inside a base85 group");
+                }
+                out.extend_from_slice(&[0; 4]);
+                continue;
+            }
+            let digit = self
+                .chars
+                .iter()
+                .position(|&d| d == c)
+                .ok_or_else(|| anyhow::anyhow!("Invalid base85 character: {}", c as char))?;
+            group.push(digit as u8);
+            if group.len() == 5 {
+                flush(&group, &mut out)?;
+                group.clear();
+            }
+        }
+        match group.len() {
+            0 => {}
+            1 => anyhow::bail!("Truncated base85 input"),
+            _ if !self.partial => anyhow::bail!("Z85 input length must be a multiple of 5"),
+            _ => flush(&group, &mut out)?,
+        }
+        Ok(out)
+    }
+
+    // Ascii85's `z` groups and `<~ ~>` delimiters don't split at fixed places
+    fn group(&self) -> Option<(usize, usize)> {
+        (!self.zero_group && !self.delimiters).then_some((4, 5))
+    }
+}
+
+struct Percent;
+
+impl Codec for Percent {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<String> {
+        Ok(percent_encode(data, PERCENT_ENCODE).to_string())
+    }
+
+    fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(percent_decode_str(text).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_round_trip() -> anyhow::Result<()> {
+        let inputs: [&[u8]; 5] = [
+            b"",
+            b"\x00\x00\x00\x00",
+            b"hello world",
+            b"\xff\xfe\x00rcli",
+            &[0xab; 64],
+        ];
+        for format in CodecFormat::ALL {
+            let codec = codec(format);
+            for input in inputs {
+                if format == CodecFormat::Z85 && !input.len().is_multiple_of(4) {
+                    assert!(codec.encode(input).is_err());
+                    continue;
+                }
+                let encoded = codec.encode(input)?;
+                assert_eq!(codec.decode(&encoded)?, input, "{} {:?}", format, encoded);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec_known_vectors() -> anyhow::Result<()> {
+        for (format, input, expected) in [
+            (CodecFormat::Hex, &b"rcli"[..], "72636c69"),
+            (CodecFormat::HexUpper, b"\xde\xad", "DEAD"),
+            (CodecFormat::Base32, b"foobar", "MZXW6YTBOI======"),
+            (CodecFormat::Base32Crockford, b"foobar", "CSQPYRK1E8"),
+            (CodecFormat::Base58, b"hello world", "StV1DL6CwTryKyV"),
+            (
+                CodecFormat::Base58Check,
+                b"hello world",
+                "3vQB7B6MrGQZaxCuFg4oh",
+            ),
+            (CodecFormat::Ascii85, b"Man ", "<~9jqo^~>"),
+            (
+                CodecFormat::Z85,
+                b"\x86\x4F\xD2\x6F\xB5\x59\xF7\x5B",
+                "HelloWorld",
+            ),
+            (CodecFormat::Percent, b"a b/c?d=e", "a%20b%2Fc%3Fd%3De"),
+        ] {
+            assert_eq!(codec(format).encode(input)?, expected, "{}", format);
+        }
+        // crockford decoding maps look-alike characters
+        assert_eq!(
+            codec(CodecFormat::Base32Crockford).decode("csqpyrkle8")?,
+            b"foobar"
+        );
+        assert!(
+            codec(CodecFormat::Base58Check)
+                .decode("3vQB7B6MrGQZaxCuFg4oi")
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec_streaming_matches_whole_input() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for format in CodecFormat::ALL {
+            // several chunks for the streamed codecs, a multiple of 4 for Z85;
+            // base58 is quadratic, so the others get a small input
+            let len = if codec(format).group().is_some() {
+                200_000
+            } else {
+                4_000
+            };
+            let data: Vec<u8> = (0..len).map(|i: u32| (i * 7 % 251) as u8).collect();
+            let input = dir.path().join(format!("data-{}.bin", format));
+            std::fs::write(&input, &data)?;
+            let input = input.to_string_lossy().into_owned();
+            let mut encoded = Vec::new();
+            process_encode(
+                EncodeOpts {
+                    input,
+                    output: None,
+                    codec: format,
+                },
+                &mut encoded,
+            )?;
+            let expected = codec(format).encode(&data)? + "\n";
+            assert!(encoded == expected.as_bytes(), "{}", format);
+
+            // line breaks shift the groups against the read chunks
+            if codec(format).group().is_some() {
+                let lines: Vec<_> = expected.as_bytes().chunks(76).collect();
+                encoded = lines.join(&b"\r\n"[..]);
+            }
+            let path = dir.path().join(format!("data.{}", format));
+            std::fs::write(&path, &encoded)?;
+            let mut decoded = Vec::new();
+            process_decode(
+                DecodeOpts {
+                    input: path.to_string_lossy().into_owned(),
+                    output: None,
+                    codec: Some(format),
+                    auto: false,
+                },
+                &mut decoded,
+            )?;
+            assert!(decoded == data, "{}", format);
+        }
+        Ok(())
+    }
+
+    fn peel(data: &[u8]) -> anyhow::Result<(Vec<u8>, String)> {
+        let (data, layers) = peel_layers(data.to_vec())?;
+        Ok((data, layers.join(" -> ")))
+    }
+
+    #[test]
+    fn test_peel_nested_layers() -> anyhow::Result<()> {
+        let json = br#"{"user":"alice","admin":false}"#;
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut gz, json)?;
+        let gzipped = gz.finish()?;
+
+        let b64 = base64_encode(Base64Format::Standard, &gzipped);
+        assert_eq!(
+            peel(b64.as_bytes())?,
+            (
+                json.to_vec(),
+                "base64 (standard) -> gzip -> json".to_string()
+            )
+        );
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut gz, b"\x00\x01binary\xff")?;
+        let url = base64_encode(Base64Format::UrlSafe, &gz.finish()?);
+        let hex = HEXLOWER.encode(url.as_bytes());
+        assert_eq!(
+            peel(hex.as_bytes())?.1,
+            "hex -> base64 (urlsafe) -> gzip -> binary"
+        );
+
+        let zlib = {
+            let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+            std::io::Write::write_all(&mut z, b"a%20b")?;
+            z.finish()?
+        };
+        let zstd = zstd::encode_all(&zlib[..], 3)?;
+        assert_eq!(
+            peel(&zstd)?,
+            (
+                b"a b".to_vec(),
+                "zstd -> deflate -> percent -> text".to_string()
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_peel_leaves_plain_text() -> anyhow::Result<()> {
+        for text in [
+            "hello",
+            "password",
+            "Hello, world!",
+            "deadbeef is a word",
+            // hex and base64 of random bytes, not worth peeling
+            "1234abcd",
+            "user2024",
+            "deadbeefcafe",
+            "admin123",
+        ] {
+            assert_eq!(
+                peel(text.as_bytes())?,
+                (text.as_bytes().to_vec(), "text".to_string())
+            );
+        }
+        assert_eq!(
+            peel(b"12345678")?,
+            (b"12345678".to_vec(), "json".to_string())
+        );
+        assert_eq!(
+            peel(b"\"aGVsbG8=\"")?.1,
+            "json string -> base64 (standard) -> text"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_peel_caps_decompressed_size() -> anyhow::Result<()> {
+        let filler = vec![0xffu8; MAX_DECOMPRESSED as usize + 1];
+        let bomb = base64_encode(Base64Format::Standard, &zstd::encode_all(&filler[..], 1)?);
+        assert!(bomb.len() < 64 * 1024);
+        let err = peel_layers(bomb.into_bytes()).unwrap_err();
+        assert!(err.to_string().contains("more than 64 MiB"), "{}", err);
+
+        let fits = zstd::encode_all(&filler[..MAX_DECOMPRESSED as usize], 1)?;
+        assert_eq!(peel(&fits)?.1, "zstd -> binary");
+        Ok(())
+    }
+}
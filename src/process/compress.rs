@@ -0,0 +1,396 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+};
+
+use flate2::{
+    Compression,
+    read::{MultiGzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+
+use crate::{CompressFormat, CompressOpts, DecompressOpts, get_reader};
+
+// brotli's own CLI defaults to quality 11 with a 4 MiB window
+const BROTLI_DEFAULT_QUALITY: u32 = 11;
+const BROTLI_LGWIN: u32 = 22;
+const BROTLI_BUFFER: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressStats {
+    pub input: u64,
+    pub output: u64,
+}
+
+impl CompressStats {
+    /// Size of the compressed side relative to the uncompressed one.
+    pub fn ratio(&self) -> f64 {
+        let (compressed, plain) = if self.output <= self.input {
+            (self.output, self.input)
+        } else {
+            (self.input, self.output)
+        };
+        if plain == 0 {
+            1.0
+        } else {
+            compressed as f64 / plain as f64
+        }
+    }
+}
+
+impl Display for CompressStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> {} bytes ({:.1}%)",
+            self.input,
+            self.output,
+            self.ratio() * 100.0
+        )
+    }
+}
+
+pub fn process_compress<W: Write>(opts: CompressOpts, writer: W) -> anyhow::Result<CompressStats> {
+    let mut reader = get_reader(&opts.input)?;
+    let counter = CountingWriter::new(BufWriter::new(writer));
+    let mut compressor = Compressor::new(counter, opts.format, opts.level)?;
+    let input = io::copy(&mut reader, &mut compressor)?;
+    let mut counter = compressor.finish()?;
+    counter.flush()?;
+    Ok(CompressStats {
+        input,
+        output: counter.count,
+    })
+}
+
+pub fn process_decompress<W: Write>(
+    opts: DecompressOpts,
+    writer: W,
+) -> anyhow::Result<CompressStats> {
+    let reader = CountingReader::new(BufReader::new(get_reader(&opts.input)?));
+    let mut reader = BufReader::new(reader);
+    let format = match opts.format {
+        Some(format) => format,
+        None => detect_compression(reader.fill_buf()?).ok_or_else(|| {
+            anyhow::anyhow!("Can't detect the compression format, pass it with --format")
+        })?,
+    };
+    let mut writer = BufWriter::new(writer);
+    let output = io::copy(&mut decompress_reader(format, &mut reader)?, &mut writer)?;
+    writer.flush()?;
+    Ok(CompressStats {
+        input: reader.get_ref().count,
+        output,
+    })
+}
+
+/// Recognizes gzip, zstd and zlib (`Deflate`) by their magic bytes. Brotli
+/// streams have no signature and are never detected.
+pub fn detect_compression(data: &[u8]) -> Option<CompressFormat> {
+    if data.starts_with(b"\x1f\x8b") {
+        Some(CompressFormat::Gzip)
+    } else if data.starts_with(b"\x28\xb5\x2f\xfd") {
+        Some(CompressFormat::Zstd)
+    } else if data.len() >= 2
+        && data[0] & 0x0f == 8
+        && u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31)
+    {
+        Some(CompressFormat::Deflate)
+    } else {
+        None
+    }
+}
+
+/// Wraps `reader` in a streaming decompressor for `format`.
+pub fn decompress_reader<'a, R: Read + 'a>(
+    format: CompressFormat,
+    reader: R,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match format {
+        CompressFormat::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        CompressFormat::Deflate => Box::new(ZlibDecoder::new(reader)),
+        CompressFormat::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        CompressFormat::Brotli => Box::new(brotli::Decompressor::new(reader, BROTLI_BUFFER)),
+    })
+}
+
+/// `Write` adapter compressing into `W`. `finish` must be called to write
+/// the end of the stream.
+pub enum Compressor<W: Write> {
+    Gzip(GzEncoder<W>),
+    Deflate(ZlibEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Brotli(Box<brotli::CompressorWriter<ErrorLatch<W>>>),
+}
+
+impl<W: Write> Compressor<W> {
+    /// `level` defaults to each format's usual default when `None`.
+    pub fn new(writer: W, format: CompressFormat, level: Option<u32>) -> anyhow::Result<Self> {
+        let max = match format {
+            CompressFormat::Gzip | CompressFormat::Deflate => 9,
+            CompressFormat::Zstd => 22,
+            CompressFormat::Brotli => 11,
+        };
+        if let Some(level) = level
+            && level > max
+        {
+            anyhow::bail!("{} compression level must be at most {}", format, max);
+        }
+        let flate_level = level.map_or_else(Compression::default, Compression::new);
+        Ok(match format {
+            CompressFormat::Gzip => Compressor::Gzip(GzEncoder::new(writer, flate_level)),
+            CompressFormat::Deflate => Compressor::Deflate(ZlibEncoder::new(writer, flate_level)),
+            // zstd treats 0 as its default level
+            CompressFormat::Zstd => {
+                Compressor::Zstd(zstd::Encoder::new(writer, level.unwrap_or(0) as i32)?)
+            }
+            CompressFormat::Brotli => Compressor::Brotli(Box::new(brotli::CompressorWriter::new(
+                ErrorLatch::new(writer),
+                BROTLI_BUFFER,
+                level.unwrap_or(BROTLI_DEFAULT_QUALITY),
+                BROTLI_LGWIN,
+            ))),
+        })
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Compressor::Gzip(encoder) => encoder.finish(),
+            Compressor::Deflate(encoder) => encoder.finish(),
+            Compressor::Zstd(encoder) => encoder.finish(),
+            Compressor::Brotli(encoder) => encoder.into_inner().into_result(),
+        }
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Compressor::Gzip(encoder) => encoder.write(buf),
+            Compressor::Deflate(encoder) => encoder.write(buf),
+            Compressor::Zstd(encoder) => encoder.write(buf),
+            Compressor::Brotli(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Compressor::Gzip(encoder) => encoder.flush(),
+            Compressor::Deflate(encoder) => encoder.flush(),
+            Compressor::Zstd(encoder) => encoder.flush(),
+            Compressor::Brotli(encoder) => encoder.flush(),
+        }
+    }
+}
+
+// brotli's `into_inner` writes the end of the stream and drops any error, so
+// the first one is kept here to be returned by `finish`.
+pub struct ErrorLatch<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W> ErrorLatch<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    fn into_result(self) -> io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.inner),
+        }
+    }
+
+    fn latch<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        if let Err(e) = &result
+            && self.error.is_none()
+        {
+            self.error = Some(io::Error::new(e.kind(), e.to_string()));
+        }
+        result
+    }
+}
+
+impl<W: Write> Write for ErrorLatch<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = match self.inner.write(buf) {
+            Ok(0) if !buf.is_empty() => Err(io::ErrorKind::WriteZero.into()),
+            result => result,
+        };
+        self.latch(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.latch(result)
+    }
+}
+
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [CompressFormat; 4] = [
+        CompressFormat::Gzip,
+        CompressFormat::Zstd,
+        CompressFormat::Brotli,
+        CompressFormat::Deflate,
+    ];
+
+    fn compress(
+        format: CompressFormat,
+        level: Option<u32>,
+        data: &[u8],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut compressor = Compressor::new(Vec::new(), format, level)?;
+        compressor.write_all(data)?;
+        Ok(compressor.finish()?)
+    }
+
+    #[test]
+    fn test_compress_round_trip() -> anyhow::Result<()> {
+        let data = std::fs::read("Cargo.lock")?;
+        for format in ALL {
+            for level in [None, Some(1)] {
+                let compressed = compress(format, level, &data)?;
+                assert!(compressed.len() < data.len() / 2, "{}", format);
+                if format != CompressFormat::Brotli {
+                    assert_eq!(detect_compression(&compressed), Some(format));
+                }
+                let mut decompressed = Vec::new();
+                decompress_reader(format, &compressed[..])?.read_to_end(&mut decompressed)?;
+                assert_eq!(decompressed, data, "{}", format);
+            }
+        }
+        assert!(Compressor::new(Vec::new(), CompressFormat::Gzip, Some(10)).is_err());
+        Ok(())
+    }
+
+    // accepts `.0` more bytes, then fails like a full disk
+    struct FullWriter(usize);
+
+    impl Write for FullWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::other("disk full"));
+            }
+            let n = buf.len().min(self.0);
+            self.0 -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_finish_reports_write_errors() -> anyhow::Result<()> {
+        let data = std::fs::read("Cargo.toml")?;
+        for format in ALL {
+            let mut compressor = Compressor::new(FullWriter(16), format, None)?;
+            let written = compressor.write_all(&data);
+            assert!(
+                written.is_err() || compressor.finish().is_err(),
+                "{}",
+                format
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_brotli_finish_reports_closing_write_error() -> anyhow::Result<()> {
+        let data = std::fs::read("Cargo.toml")?;
+        let mut reference = brotli::CompressorWriter::new(
+            Vec::new(),
+            BROTLI_BUFFER,
+            BROTLI_DEFAULT_QUALITY,
+            BROTLI_LGWIN,
+        );
+        reference.write_all(&data)?;
+        reference.flush()?;
+        let flushed = reference.get_ref().len();
+
+        // everything up to the end of the stream fits, the closing bytes don't
+        let mut compressor = Compressor::new(FullWriter(flushed), CompressFormat::Brotli, None)?;
+        compressor.write_all(&data)?;
+        compressor.flush()?;
+        assert!(compressor.finish().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_compress_stats() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("compress.zst");
+        let stats = process_compress(
+            CompressOpts {
+                input: "Cargo.toml".to_string(),
+                output: "-".to_string(),
+                format: CompressFormat::Zstd,
+                level: Some(19),
+            },
+            std::fs::File::create(&path)?,
+        )?;
+        assert_eq!(stats.input, std::fs::metadata("Cargo.toml")?.len());
+        assert_eq!(stats.output, std::fs::metadata(&path)?.len());
+        assert!(stats.ratio() < 1.0);
+
+        let mut decompressed = Vec::new();
+        let back = process_decompress(
+            DecompressOpts {
+                input: path.to_string_lossy().into_owned(),
+                output: "-".to_string(),
+                format: None,
+            },
+            &mut decompressed,
+        )?;
+        assert_eq!(back.input, stats.output);
+        assert_eq!(decompressed, std::fs::read("Cargo.toml")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use std::{io::Read, path::Path};
+
+use percent_encoding::percent_decode_str;
+
+use crate::{
+    Base64DataUriDecodeOpts, Base64DataUriOpts, Base64Format, base64_decode_lenient, base64_encode,
+    get_reader,
+};
+
+// RFC 2397: a data URI without a media type is US-ASCII text
+const DEFAULT_MIME: &str = "text/plain;charset=US-ASCII";
+const OCTET_STREAM: &str = "application/octet-stream";
+
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"OTTO", "font/otf"),
+    (b"\x00asm", "application/wasm"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+];
+
+// Short enough to start plain text or other binary formats, so they only
+// count when the file extension says nothing
+const WEAK_MAGIC: &[(&[u8], &str)] = &[
+    (b"BM", "image/bmp"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"\x00\x01\x00\x00", "font/ttf"),
+];
+
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("ico", "image/x-icon"),
+    ("svg", "image/svg+xml"),
+    ("pdf", "application/pdf"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("wasm", "application/wasm"),
+    ("json", "application/json"),
+    ("js", "text/javascript"),
+    ("css", "text/css"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct DataUri {
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+pub fn process_datauri_encode(opts: Base64DataUriOpts) -> anyhow::Result<String> {
+    let mut reader = get_reader(&opts.input)?;
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+
+    let path = (opts.input != "-").then_some(opts.input.as_str());
+    let mime = match &opts.mime {
+        Some(mime) => mime.as_str(),
+        None => sniff_mime(&buf, path),
+    };
+    Ok(datauri_encode(mime, &buf))
+}
+
+pub fn process_datauri_decode(opts: Base64DataUriDecodeOpts) -> anyhow::Result<DataUri> {
+    let mut reader = get_reader(&opts.input)?;
+    let mut buf = String::new();
+    reader.read_to_string(&mut buf)?;
+    parse_datauri(buf.trim())
+}
+
+pub fn datauri_encode(mime: &str, data: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime,
+        base64_encode(Base64Format::Standard, data)
+    )
+}
+
+/// Parses `data:[<mediatype>][;base64],<data>`, where the payload is
+/// percent-encoded unless `;base64` is given.
+pub fn parse_datauri(uri: &str) -> anyhow::Result<DataUri> {
+    let rest = uri
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &uri[5..])
+        .ok_or_else(|| anyhow::anyhow!("Not a data URI"))?;
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| anyhow::anyhow!("Data URI has no ',' before its payload"))?;
+
+    let (mime, is_base64) = match header.rsplit_once(';') {
+        Some((mime, param)) if param.eq_ignore_ascii_case("base64") => (mime, true),
+        _ => (header, false),
+    };
+    let mime = if mime.is_empty() {
+        DEFAULT_MIME.to_string()
+    } else if mime.starts_with(';') {
+        format!("text/plain{}", mime)
+    } else {
+        mime.to_string()
+    };
+
+    let data = if is_base64 {
+        // payloads copied out of HTML are often still percent-encoded or wrapped
+        let payload = percent_decode_str(payload).decode_utf8()?;
+        base64_decode_lenient(&payload)?.0
+    } else {
+        percent_decode_str(payload).collect()
+    };
+    Ok(DataUri { mime, data })
+}
+
+/// Picks a MIME type from the leading magic bytes, falling back to the file
+/// extension, then to weak two or four byte magics, and then
+/// `application/octet-stream`.
+pub fn sniff_mime(data: &[u8], path: Option<&str>) -> &'static str {
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return mime;
+    }
+    if data.len() >= 12 && &data[..4] == b"RIFF" {
+        match &data[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            _ => {}
+        }
+    }
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return if &data[8..12] == b"avif" {
+            "image/avif"
+        } else {
+            "video/mp4"
+        };
+    }
+    let extension = path
+        .and_then(|path| Path::new(path).extension())
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    if let Some(extension) = extension
+        && let Some((_, mime)) = EXTENSIONS.iter().find(|(ext, _)| *ext == extension)
+    {
+        return mime;
+    }
+    if let Some((_, mime)) = WEAK_MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return mime;
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(512)]);
+    if head.contains("<svg") {
+        "image/svg+xml"
+    } else {
+        OCTET_STREAM
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_mime() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\0\0", None), "image/png");
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 ", None), "image/webp");
+        // magic bytes win over a misleading extension
+        assert_eq!(sniff_mime(b"GIF89a", Some("logo.png")), "image/gif");
+        assert_eq!(sniff_mime(b"body {}", Some("site.CSS")), "text/css");
+        assert_eq!(sniff_mime(b"BMW,BMX\n", Some("bikes.csv")), "text/csv");
+        assert_eq!(sniff_mime(b"BM\x36\x00\x00\x00", None), "image/bmp");
+        assert_eq!(
+            sniff_mime(b"\0\0\x01\0\x01\0", Some("favicon.ico")),
+            "image/x-icon"
+        );
+        assert_eq!(
+            sniff_mime(b"\0\0\x01\0\x01\0", Some("raw.txt")),
+            "text/plain"
+        );
+        assert_eq!(
+            sniff_mime(b"<?xml version=\"1.0\"?><svg/>", None),
+            "image/svg+xml"
+        );
+        // any big-endian u32 of 65536 looks like TrueType
+        assert_eq!(sniff_mime(b"\0\x01\0\0\0\x0c", None), "font/ttf");
+        assert_eq!(
+            sniff_mime(b"\0\x01\0\0\0\x0c", Some("counts.txt")),
+            "text/plain"
+        );
+        assert_eq!(sniff_mime(b"\x01\x02", None), OCTET_STREAM);
+    }
+
+    #[test]
+    fn test_datauri_round_trip() -> anyhow::Result<()> {
+        let data = b"\x89PNG\r\n\x1a\n\xff\x00".to_vec();
+        let uri = datauri_encode(sniff_mime(&data, None), &data);
+        assert_eq!(uri, "data:image/png;base64,iVBORw0KGgr/AA==");
+        assert_eq!(
+            parse_datauri(&uri)?,
+            DataUri {
+                mime: "image/png".to_string(),
+                data
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_datauri() -> anyhow::Result<()> {
+        let uri = parse_datauri("data:,A%20brief%20note")?;
+        assert_eq!(uri.mime, DEFAULT_MIME);
+        assert_eq!(uri.data, b"A brief note");
+
+        let uri = parse_datauri("DATA:;charset=utf-8;base64,aGk=")?;
+        assert_eq!(uri.mime, "text/plain;charset=utf-8");
+        assert_eq!(uri.data, b"hi");
+
+        assert!(parse_datauri("http://example.com").is_err());
+        assert!(parse_datauri("data:text/plain").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,777 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+use aes_gcm::Aes256Gcm;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    ChaCha20Poly1305, XChaCha20Poly1305,
+    aead::{
+        KeyInit, Payload,
+        stream::{DecryptorBE32, EncryptorBE32},
+    },
+};
+use rand::{RngCore, rngs::OsRng};
+use zeroize::Zeroizing;
+
+use crate::{
+    Base64Format, Base64Reader, Base64Writer, KeyKind, TextCipher, TextDecryptOpts,
+    TextEncryptOpts, base64_decode, base64_encode, get_reader, key_id, read_key, read_secret,
+};
+
+pub const ENCRYPTED_VERSION: u32 = 1;
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+const TAG_SIZE: usize = 16;
+const BEGIN: &str = "-----BEGIN RCLI ENCRYPTED MESSAGE-----";
+const END: &str = "-----END RCLI ENCRYPTED MESSAGE-----";
+const ARMOR_WIDTH: usize = 64;
+// Every message gets a fresh salt: the passphrase is stretched with it, a
+// key file is turned into a message key with it. 16 bytes is the least a
+// message may carry.
+const SALT_LEN: usize = 32;
+const MIN_SALT_LEN: usize = 16;
+// Changing this makes every key file message undecryptable.
+const MESSAGE_KEY_CONTEXT: &str = "rcli 2025-10-01 encrypt message key v1";
+// m_cost is in KiB. 8 MiB is as low as a passphrase should go, 4 GiB is
+// more likely a hostile header than a real setting
+const MIN_KDF_M_COST: u32 = 8 * 1024;
+const MAX_KDF_M_COST: u32 = 4 * 1024 * 1024;
+const MAX_KDF_T_COST: u32 = 64;
+const MAX_KDF_P_COST: u32 = 16;
+
+/// What a message is encrypted with: a key file or a passphrase that is
+/// stretched with Argon2id. Both are wiped on drop.
+pub enum MessageKey {
+    Symmetric(Zeroizing<Vec<u8>>),
+    Passphrase(Zeroizing<String>),
+}
+
+/// Argon2id cost, stored in the header so decryption doesn't depend on the
+/// defaults of the rcli version that encrypted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KdfParams {
+    /// Checks the costs against the same bounds for encrypting and for the
+    /// headers of messages to decrypt, so every message rcli writes can be
+    /// read back.
+    pub fn new(m_cost: u32, t_cost: u32, p_cost: u32) -> anyhow::Result<Self> {
+        if !(MIN_KDF_M_COST..=MAX_KDF_M_COST).contains(&m_cost) {
+            anyhow::bail!(
+                "Argon2 m_cost must be {} to {} KiB, got {}",
+                MIN_KDF_M_COST,
+                MAX_KDF_M_COST,
+                m_cost
+            );
+        }
+        if !(1..=MAX_KDF_T_COST).contains(&t_cost) {
+            anyhow::bail!(
+                "Argon2 t_cost must be 1 to {}, got {}",
+                MAX_KDF_T_COST,
+                t_cost
+            );
+        }
+        if !(1..=MAX_KDF_P_COST).contains(&p_cost) {
+            anyhow::bail!(
+                "Argon2 p_cost must be 1 to {}, got {}",
+                MAX_KDF_P_COST,
+                p_cost
+            );
+        }
+        Ok(Self {
+            m_cost,
+            t_cost,
+            p_cost,
+        })
+    }
+
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))?;
+        let mut key = Zeroizing::new(vec![0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(
+            passphrase.as_bytes(),
+            salt,
+            &mut key,
+        )?;
+        Ok(key)
+    }
+}
+
+impl std::fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "m={},t={},p={}", self.m_cost, self.t_cost, self.p_cost)
+    }
+}
+
+impl std::str::FromStr for KdfParams {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut m_cost, mut t_cost, mut p_cost) = (None, None, None);
+        for param in s.split(',') {
+            let (name, value) = param
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid Kdf-Params: {}", s))?;
+            let value = value.parse::<u32>()?;
+            match name {
+                "m" => m_cost = Some(value),
+                "t" => t_cost = Some(value),
+                "p" => p_cost = Some(value),
+                _ => anyhow::bail!("Invalid Kdf-Params: {}", s),
+            }
+        }
+        match (m_cost, t_cost, p_cost) {
+            (Some(m_cost), Some(t_cost), Some(p_cost)) => KdfParams::new(m_cost, t_cost, p_cost)
+                .map_err(|e| anyhow::anyhow!("Invalid Kdf-Params: {}: {}", s, e)),
+            _ => anyhow::bail!("Invalid Kdf-Params: {}", s),
+        }
+    }
+}
+
+/// Everything needed to decrypt besides the key. The header text is part of
+/// the associated data of every chunk, so none of it can be altered.
+/// Messages carry a `key_id` when encrypted with a key file and a `kdf` when
+/// encrypted with a passphrase, and a `salt` either way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptionHeader {
+    pub version: u32,
+    pub cipher: TextCipher,
+    pub key_id: Option<String>,
+    pub kdf: Option<KdfParams>,
+    pub salt: Vec<u8>,
+    pub chunk_size: usize,
+    pub nonce: Vec<u8>,
+}
+
+impl EncryptionHeader {
+    fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\nVersion: {}\nCipher: {}\n",
+            BEGIN, self.version, self.cipher
+        );
+        if let Some(key_id) = &self.key_id {
+            text.push_str(&format!("Key-Id: {}\n", key_id));
+        }
+        if let Some(kdf) = &self.kdf {
+            text.push_str(&format!("Kdf: argon2id\nKdf-Params: {}\n", kdf));
+        }
+        text.push_str(&format!(
+            "Salt: {}\nChunk-Size: {}\nNonce: {}\n\n",
+            base64_encode(Base64Format::Standard, &self.salt),
+            self.chunk_size,
+            base64_encode(Base64Format::Standard, &self.nonce)
+        ));
+        text
+    }
+
+    /// Reads up to and including the blank line after the headers, and
+    /// returns the header with its text normalized to `\n` line endings.
+    fn read(reader: &mut impl BufRead) -> anyhow::Result<(Self, String)> {
+        let mut text = String::new();
+        let (mut version, mut cipher, mut key_id, mut chunk_size, mut nonce) =
+            (None, None, None, None, None);
+        let (mut kdf, mut kdf_params, mut salt) = (None, None, None);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                anyhow::bail!("Truncated encrypted message header");
+            }
+            let trimmed = line.trim_end();
+            if text.is_empty() {
+                if trimmed != BEGIN {
+                    anyhow::bail!("Not an rcli encrypted message");
+                }
+            } else if trimmed.is_empty() {
+                text.push('\n');
+                break;
+            } else {
+                let (name, value) = trimmed
+                    .split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("Invalid header: {}", trimmed))?;
+                let value = value.trim();
+                match name {
+                    "Version" => version = Some(value.parse::<u32>()?),
+                    "Cipher" => cipher = Some(value.parse::<TextCipher>()?),
+                    "Key-Id" => key_id = Some(value.to_string()),
+                    "Kdf" => kdf = Some(value.to_string()),
+                    "Kdf-Params" => kdf_params = Some(value.parse::<KdfParams>()?),
+                    "Salt" => salt = Some(base64_decode(Base64Format::Standard, value)?),
+                    "Chunk-Size" => chunk_size = Some(value.parse::<usize>()?),
+                    "Nonce" => nonce = Some(base64_decode(Base64Format::Standard, value)?),
+                    _ => {}
+                }
+            }
+            text.push_str(trimmed);
+            text.push('\n');
+        }
+
+        let version = version.ok_or_else(|| anyhow::anyhow!("Message has no Version"))?;
+        if version != ENCRYPTED_VERSION {
+            anyhow::bail!("Unsupported encrypted message version: {}", version);
+        }
+        let cipher = cipher.ok_or_else(|| anyhow::anyhow!("Message has no Cipher"))?;
+        let chunk_size = chunk_size.ok_or_else(|| anyhow::anyhow!("Message has no Chunk-Size"))?;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            anyhow::bail!("Invalid Chunk-Size: {}", chunk_size);
+        }
+        let nonce = nonce.ok_or_else(|| anyhow::anyhow!("Message has no Nonce"))?;
+        if nonce.len() != cipher.nonce_len() {
+            anyhow::bail!("{} nonce must be {} bytes", cipher, cipher.nonce_len());
+        }
+        let kdf = match (kdf.as_deref(), kdf_params) {
+            (None, None) => None,
+            (Some("argon2id"), Some(params)) => Some(params),
+            (Some(kdf), _) if kdf != "argon2id" => anyhow::bail!("Unsupported Kdf: {}", kdf),
+            _ => anyhow::bail!("Message has an incomplete Kdf or Kdf-Params"),
+        };
+        if key_id.is_none() == kdf.is_none() {
+            anyhow::bail!("Message needs either a Key-Id or a Kdf");
+        }
+        let salt = salt.ok_or_else(|| anyhow::anyhow!("Message has no Salt"))?;
+        if salt.len() < MIN_SALT_LEN {
+            anyhow::bail!("Salt must be at least {} bytes", MIN_SALT_LEN);
+        }
+        let header = Self {
+            version,
+            cipher,
+            key_id,
+            kdf,
+            salt,
+            chunk_size,
+            nonce,
+        };
+        Ok((header, text))
+    }
+}
+
+pub fn process_text_encrypt<W: Write>(opts: TextEncryptOpts, writer: W) -> anyhow::Result<()> {
+    let kdf = opts.kdf_params()?;
+    let key = match &opts.key {
+        Some(key) => MessageKey::Symmetric(read_key(key, KeyKind::Symmetric)?),
+        None => MessageKey::Passphrase(read_passphrase(opts.passphrase_fd, true)?),
+    };
+    let mut reader = get_reader(&opts.input)?;
+    let aad = opts.aad.unwrap_or_default();
+    encrypt(
+        &key,
+        opts.cipher,
+        kdf,
+        aad.as_bytes(),
+        DEFAULT_CHUNK_SIZE,
+        &mut reader,
+        writer,
+    )
+}
+
+pub fn process_text_decrypt<W: Write>(
+    opts: TextDecryptOpts,
+    writer: W,
+) -> anyhow::Result<EncryptionHeader> {
+    let key = match &opts.key {
+        Some(key) => MessageKey::Symmetric(read_key(key, KeyKind::Symmetric)?),
+        None => MessageKey::Passphrase(read_passphrase(opts.passphrase_fd, false)?),
+    };
+    let reader = get_reader(&opts.input)?;
+    let aad = opts.aad.unwrap_or_default();
+    decrypt(&key, aad.as_bytes(), reader, writer)
+}
+
+/// Reads the first line of file descriptor `fd`, or prompts on the terminal
+/// without echo (twice when encrypting, to catch typos).
+pub fn read_passphrase(fd: Option<i32>, confirm: bool) -> anyhow::Result<Zeroizing<String>> {
+    if let Some(fd) = fd {
+        return read_secret(&format!("/dev/fd/{}", fd), "");
+    }
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Passphrase: ")?);
+    if passphrase.is_empty() {
+        anyhow::bail!("Passphrase must not be empty");
+    }
+    if confirm {
+        let confirmation = Zeroizing::new(rpassword::prompt_password("Confirm passphrase: ")?);
+        if confirmation != passphrase {
+            anyhow::bail!("Passphrases don't match");
+        }
+    }
+    Ok(passphrase)
+}
+
+/// Encrypts `reader` in `chunk_size` pieces with the STREAM construction
+/// (BE32 counter and last-chunk flag), so chunks can't be reordered, dropped
+/// or truncated, and writes the armored message.
+/// `kdf` is only used for passphrases.
+pub fn encrypt(
+    key: &MessageKey,
+    cipher: TextCipher,
+    kdf: KdfParams,
+    aad: &[u8],
+    chunk_size: usize,
+    reader: &mut dyn Read,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    let mut nonce = vec![0u8; cipher.nonce_len()];
+    OsRng.fill_bytes(&mut nonce);
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let (key, key_id, kdf) = match key {
+        MessageKey::Symmetric(key) => (
+            message_key(key, &salt),
+            Some(key_id(KeyKind::Symmetric, key)?),
+            None,
+        ),
+        MessageKey::Passphrase(passphrase) => (kdf.derive_key(passphrase, &salt)?, None, Some(kdf)),
+    };
+    let header = EncryptionHeader {
+        version: ENCRYPTED_VERSION,
+        cipher,
+        key_id,
+        kdf,
+        salt,
+        chunk_size,
+        nonce,
+    };
+    let header_text = header.to_text();
+    let aad = [header_text.as_bytes(), aad].concat();
+
+    let mut writer = BufWriter::new(writer);
+    writer.write_all(header_text.as_bytes())?;
+    let mut body = Base64Writer::new(writer, Base64Format::Standard, ARMOR_WIDTH, false);
+    let sealer = Sealer::new(&header, &key)?;
+    seal_chunks(sealer, chunk_size, &aad, reader, &mut body)?;
+    let mut writer = body.finish()?;
+    write!(writer, "\n{}\n", END)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Decrypts an armored message into `writer`. Chunks are written as soon as
+/// they are authenticated, so on error the output holds a verified prefix
+/// that must be discarded.
+pub fn decrypt(
+    key: &MessageKey,
+    aad: &[u8],
+    reader: impl Read,
+    writer: impl Write,
+) -> anyhow::Result<EncryptionHeader> {
+    let mut reader = BufReader::new(reader);
+    let (header, header_text) = EncryptionHeader::read(&mut reader)?;
+    let key = match (key, &header.key_id, &header.kdf) {
+        (MessageKey::Symmetric(key), Some(id), _) => {
+            let expected = key_id(KeyKind::Symmetric, key)?;
+            if *id != expected {
+                anyhow::bail!("Message was encrypted with key {}, not {}", id, expected);
+            }
+            message_key(key, &header.salt)
+        }
+        (MessageKey::Passphrase(passphrase), _, Some(kdf)) => {
+            kdf.derive_key(passphrase, &header.salt)?
+        }
+        (MessageKey::Symmetric(_), None, _) => {
+            anyhow::bail!("Message was encrypted with a passphrase, not a key file")
+        }
+        (MessageKey::Passphrase(_), Some(id), _) => {
+            anyhow::bail!("Message was encrypted with key {}, pass it with -k", id)
+        }
+        (MessageKey::Passphrase(_), None, None) => {
+            unreachable!("checked by EncryptionHeader::read")
+        }
+    };
+    let aad = [header_text.as_bytes(), aad].concat();
+
+    let mut body = Base64Reader::new(ArmorBody::new(reader, END), Base64Format::Standard);
+    let mut writer = BufWriter::new(writer);
+    let opener = Opener::new(&header, &key)?;
+    open_chunks(opener, header.chunk_size, &aad, &mut body, &mut writer)?;
+    writer.flush()?;
+    Ok(header)
+}
+
+// A key file is reused across messages, so each message is sealed with its
+// own key. The random STREAM nonce prefix alone (7 bytes for AES-256-GCM)
+// would collide after a few thousand messages at NIST's 2^-32 bound.
+fn message_key(key: &[u8], salt: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut hasher = blake3::Hasher::new_derive_key(MESSAGE_KEY_CONTEXT);
+    hasher.update(key);
+    hasher.update(salt);
+    Zeroizing::new(hasher.finalize().as_bytes().to_vec())
+}
+
+fn new_cipher<A: KeyInit>(key: &[u8]) -> anyhow::Result<A> {
+    A::new_from_slice(key).map_err(|_| anyhow::anyhow!("Encryption key must be 32 bytes"))
+}
+
+// The nonce length was checked against the cipher when the header was built
+// or parsed, so the slice conversions below can't panic. ChaCha20Poly1305 is
+// the age payload cipher, its 11 byte counter is a BE32 one behind 7 zeros.
+pub(super) enum Sealer {
+    XChaCha20Poly1305(EncryptorBE32<XChaCha20Poly1305>),
+    Aes256Gcm(Box<EncryptorBE32<Aes256Gcm>>),
+    ChaCha20Poly1305(EncryptorBE32<ChaCha20Poly1305>),
+}
+
+impl Sealer {
+    fn new(header: &EncryptionHeader, key: &[u8]) -> anyhow::Result<Self> {
+        let nonce = header.nonce.as_slice();
+        Ok(match header.cipher {
+            TextCipher::XChaCha20Poly1305 => {
+                Sealer::XChaCha20Poly1305(EncryptorBE32::from_aead(new_cipher(key)?, nonce.into()))
+            }
+            TextCipher::Aes256Gcm => Sealer::Aes256Gcm(Box::new(EncryptorBE32::from_aead(
+                new_cipher(key)?,
+                nonce.into(),
+            ))),
+        })
+    }
+
+    pub(super) fn age(key: &[u8]) -> anyhow::Result<Self> {
+        let nonce = [0u8; 7];
+        Ok(Sealer::ChaCha20Poly1305(EncryptorBE32::from_aead(
+            new_cipher(key)?,
+            (&nonce).into(),
+        )))
+    }
+
+    fn seal_next(&mut self, payload: Payload) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Sealer::XChaCha20Poly1305(e) => e.encrypt_next(payload),
+            Sealer::Aes256Gcm(e) => e.encrypt_next(payload),
+            Sealer::ChaCha20Poly1305(e) => e.encrypt_next(payload),
+        }
+        .map_err(|_| anyhow::anyhow!("Encryption failed"))
+    }
+
+    fn seal_last(self, payload: Payload) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Sealer::XChaCha20Poly1305(e) => e.encrypt_last(payload),
+            Sealer::Aes256Gcm(e) => (*e).encrypt_last(payload),
+            Sealer::ChaCha20Poly1305(e) => e.encrypt_last(payload),
+        }
+        .map_err(|_| anyhow::anyhow!("Encryption failed"))
+    }
+}
+
+pub(super) enum Opener {
+    XChaCha20Poly1305(DecryptorBE32<XChaCha20Poly1305>),
+    Aes256Gcm(Box<DecryptorBE32<Aes256Gcm>>),
+    ChaCha20Poly1305(DecryptorBE32<ChaCha20Poly1305>),
+}
+
+impl Opener {
+    fn new(header: &EncryptionHeader, key: &[u8]) -> anyhow::Result<Self> {
+        let nonce = header.nonce.as_slice();
+        Ok(match header.cipher {
+            TextCipher::XChaCha20Poly1305 => {
+                Opener::XChaCha20Poly1305(DecryptorBE32::from_aead(new_cipher(key)?, nonce.into()))
+            }
+            TextCipher::Aes256Gcm => Opener::Aes256Gcm(Box::new(DecryptorBE32::from_aead(
+                new_cipher(key)?,
+                nonce.into(),
+            ))),
+        })
+    }
+
+    pub(super) fn age(key: &[u8]) -> anyhow::Result<Self> {
+        let nonce = [0u8; 7];
+        Ok(Opener::ChaCha20Poly1305(DecryptorBE32::from_aead(
+            new_cipher(key)?,
+            (&nonce).into(),
+        )))
+    }
+
+    fn open_next(&mut self, payload: Payload) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Opener::XChaCha20Poly1305(d) => d.decrypt_next(payload),
+            Opener::Aes256Gcm(d) => d.decrypt_next(payload),
+            Opener::ChaCha20Poly1305(d) => d.decrypt_next(payload),
+        }
+        .map_err(|_| open_error())
+    }
+
+    fn open_last(self, payload: Payload) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Opener::XChaCha20Poly1305(d) => d.decrypt_last(payload),
+            Opener::Aes256Gcm(d) => (*d).decrypt_last(payload),
+            Opener::ChaCha20Poly1305(d) => d.decrypt_last(payload),
+        }
+        .map_err(|_| open_error())
+    }
+}
+
+fn open_error() -> anyhow::Error {
+    anyhow::anyhow!("Decryption failed: wrong key or associated data, or a corrupted message")
+}
+
+pub(super) fn seal_chunks(
+    mut sealer: Sealer,
+    chunk_size: usize,
+    aad: &[u8],
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let mut chunk = vec![0u8; chunk_size];
+    let mut next = vec![0u8; chunk_size];
+    let mut len = read_full(reader, &mut chunk)?;
+    loop {
+        // only a full chunk can be followed by another one
+        let next_len = if len == chunk.len() {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        let msg = &chunk[..len];
+        if next_len == 0 {
+            writer.write_all(&sealer.seal_last(Payload { msg, aad })?)?;
+            return Ok(());
+        }
+        writer.write_all(&sealer.seal_next(Payload { msg, aad })?)?;
+        std::mem::swap(&mut chunk, &mut next);
+        len = next_len;
+    }
+}
+
+pub(super) fn open_chunks(
+    mut opener: Opener,
+    chunk_size: usize,
+    aad: &[u8],
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let mut chunk = vec![0u8; chunk_size + TAG_SIZE];
+    let mut next = vec![0u8; chunk_size + TAG_SIZE];
+    let mut len = read_full(reader, &mut chunk)?;
+    let mut first = true;
+    loop {
+        let next_len = if len == chunk.len() {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        let msg = &chunk[..len];
+        if next_len == 0 {
+            // seal_chunks reads ahead and never writes one, and age only
+            // allows an empty last chunk when it's the whole payload
+            if !first && len == TAG_SIZE {
+                anyhow::bail!("Empty last chunk after a full one");
+            }
+            writer.write_all(&opener.open_last(Payload { msg, aad })?)?;
+            return Ok(());
+        }
+        writer.write_all(&opener.open_next(Payload { msg, aad })?)?;
+        std::mem::swap(&mut chunk, &mut next);
+        len = next_len;
+        first = false;
+    }
+}
+
+// Fills `buf` unless the input ends first, unlike a single `read`.
+pub(super) fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+// Yields the base64 lines of an armored body and stops at the `end` line.
+pub(super) struct ArmorBody<R> {
+    inner: R,
+    end: &'static str,
+    line: String,
+    pos: usize,
+    done: bool,
+}
+
+impl<R> ArmorBody<R> {
+    pub(super) fn new(inner: R, end: &'static str) -> Self {
+        Self {
+            inner,
+            end,
+            line: String::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Read for ArmorBody<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.line.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.line.clear();
+            self.pos = 0;
+            if self.inner.read_line(&mut self.line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("Missing {}", self.end),
+                ));
+            }
+            if self.line.trim_end() == self.end {
+                self.line.clear();
+                self.done = true;
+            }
+        }
+        let bytes = &self.line.as_bytes()[self.pos..];
+        let n = bytes.len().min(buf.len());
+        buf[..n].copy_from_slice(&bytes[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the lowest costs allowed, the defaults make debug builds crawl
+    const TEST_KDF: KdfParams = KdfParams {
+        m_cost: MIN_KDF_M_COST,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn key() -> MessageKey {
+        MessageKey::Symmetric(Zeroizing::new(vec![42; 32]))
+    }
+
+    fn seal_with(key: &MessageKey, cipher: TextCipher, data: &[u8], aad: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encrypt(key, cipher, TEST_KDF, aad, 64, &mut &data[..], &mut out).expect("encrypt");
+        out
+    }
+
+    fn open_with(key: &MessageKey, message: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        decrypt(key, aad, message, &mut out)?;
+        Ok(out)
+    }
+
+    fn seal(cipher: TextCipher, data: &[u8], aad: &[u8]) -> Vec<u8> {
+        seal_with(&key(), cipher, data, aad)
+    }
+
+    fn open(message: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+        open_with(&key(), message, aad)
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() -> anyhow::Result<()> {
+        for cipher in [TextCipher::XChaCha20Poly1305, TextCipher::Aes256Gcm] {
+            // empty, partial, exactly one chunk and several chunks
+            for len in [0, 5, 64, 64 * 3 + 1] {
+                let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let message = seal(cipher, &data, b"ticket-42");
+                assert!(message.starts_with(BEGIN.as_bytes()));
+                assert_eq!(open(&message, b"ticket-42")?, data, "{} {}", cipher, len);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypt_nonces_are_random() {
+        let a = seal(TextCipher::XChaCha20Poly1305, b"same", b"");
+        let b = seal(TextCipher::XChaCha20Poly1305, b"same", b"");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_key_file_messages_have_their_own_key() -> anyhow::Result<()> {
+        let a = seal(TextCipher::Aes256Gcm, b"same", b"");
+        let b = seal(TextCipher::Aes256Gcm, b"same", b"");
+        let (header_a, _) = EncryptionHeader::read(&mut &a[..])?;
+        let (header_b, _) = EncryptionHeader::read(&mut &b[..])?;
+        assert_eq!(header_a.salt.len(), SALT_LEN);
+        assert_ne!(header_a.salt, header_b.salt);
+        assert_ne!(*message_key(&[42; 32], &header_a.salt), [42; 32]);
+
+        // the salt picks the key, moving it to another message breaks both
+        let a = String::from_utf8(a)?;
+        let salt = |header: &EncryptionHeader| base64_encode(Base64Format::Standard, &header.salt);
+        let swapped = a.replace(&salt(&header_a), &salt(&header_b));
+        assert!(open(swapped.as_bytes(), b"").is_err());
+        assert!(open(a.replace("Salt: ", "Salt-X: ").as_bytes(), b"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_rejects_tampering() -> anyhow::Result<()> {
+        let data = [7u8; 200];
+        let message = String::from_utf8(seal(TextCipher::XChaCha20Poly1305, &data, b"ci"))?;
+        assert!(open(message.as_bytes(), b"other").is_err());
+        assert!(
+            open(
+                message
+                    .replace("Chunk-Size: 64", "Chunk-Size: 65")
+                    .as_bytes(),
+                b"ci"
+            )
+            .is_err()
+        );
+
+        // drop the last chunk: still valid base64 but the STREAM end is missing
+        let (header, rest) = message.split_once("\n\n").expect("header");
+        let body: String = rest.lines().take_while(|line| *line != END).collect();
+        let sealed = base64_decode(Base64Format::Standard, &body)?;
+        let truncated = base64_encode(Base64Format::Standard, &sealed[..3 * (64 + TAG_SIZE)]);
+        let truncated = format!("{}\n\n{}\n{}\n", header, truncated, END);
+        assert!(open(truncated.as_bytes(), b"ci").is_err());
+
+        let other = MessageKey::Symmetric(Zeroizing::new(vec![1; 32]));
+        let err = open_with(&other, message.as_bytes(), b"ci").unwrap_err();
+        assert!(err.to_string().contains("was encrypted with key"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_passphrase_round_trip() -> anyhow::Result<()> {
+        let passphrase = MessageKey::Passphrase(Zeroizing::new("correct horse".to_string()));
+        let data = [3u8; 100];
+        let message = seal_with(&passphrase, TextCipher::Aes256Gcm, &data, b"");
+        let text = String::from_utf8(message.clone())?;
+        assert!(text.contains("Kdf: argon2id\nKdf-Params: m=8192,t=1,p=1\nSalt: "));
+        assert!(!text.contains("Key-Id"));
+        assert_eq!(open_with(&passphrase, &message, b"")?, data);
+
+        let wrong = MessageKey::Passphrase(Zeroizing::new("battery staple".to_string()));
+        assert!(open_with(&wrong, &message, b"").is_err());
+        assert!(open(&message, b"").is_err());
+        // the cost is authenticated, changing it breaks the message
+        let cheaper = text.replace("t=1", "t=2");
+        assert!(open_with(&passphrase, cheaper.as_bytes(), b"").is_err());
+        let hostile = text.replace("m=8192", "m=999999999");
+        assert!(open_with(&passphrase, hostile.as_bytes(), b"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_kdf_bounds() {
+        assert!(KdfParams::new(MIN_KDF_M_COST, 1, 1).is_ok());
+        assert!(KdfParams::new(MAX_KDF_M_COST, MAX_KDF_T_COST, MAX_KDF_P_COST).is_ok());
+        for (m, t, p) in [
+            (MIN_KDF_M_COST - 1, 1, 1),
+            (MAX_KDF_M_COST + 1, 1, 1),
+            (MIN_KDF_M_COST, 0, 1),
+            (MIN_KDF_M_COST, MAX_KDF_T_COST + 1, 1),
+            (MIN_KDF_M_COST, 1, 0),
+            (MIN_KDF_M_COST, 1, MAX_KDF_P_COST + 1),
+        ] {
+            assert!(KdfParams::new(m, t, p).is_err(), "m={},t={},p={}", m, t, p);
+            // what encrypt refuses, decrypt refuses too
+            assert!(
+                format!("m={},t={},p={}", m, t, p)
+                    .parse::<KdfParams>()
+                    .is_err()
+            );
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::{fmt::Display, fs, path::Path, str::FromStr};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use ed25519_dalek::SigningKey;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::{Base64Format, KeyFormat, base64_decode_lenient, base64_encode, detect_key_format};
+
+pub const KEY_FILE_VERSION: u32 = 1;
+const KEY_ID_CONTEXT: &str = "rcli 2026-10-19 key id v1";
+const ARMOR_PREFIX: &str = "-----BEGIN RCLI ";
+const ARMOR_WIDTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyKind {
+    Blake3,
+    Ed25519Secret,
+    Ed25519Public,
+    Symmetric,
+}
+
+impl KeyKind {
+    fn label(&self) -> &'static str {
+        match self {
+            KeyKind::Blake3 => "BLAKE3 KEY",
+            KeyKind::Ed25519Secret => "ED25519 PRIVATE KEY",
+            KeyKind::Ed25519Public => "ED25519 PUBLIC KEY",
+            KeyKind::Symmetric => "SYMMETRIC KEY",
+        }
+    }
+
+    pub fn key_len(&self) -> usize {
+        32
+    }
+
+    pub fn is_secret(&self) -> bool {
+        *self != KeyKind::Ed25519Public
+    }
+}
+
+impl FromStr for KeyKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(KeyKind::Blake3),
+            "ed25519-secret" => Ok(KeyKind::Ed25519Secret),
+            "ed25519-public" => Ok(KeyKind::Ed25519Public),
+            "symmetric" => Ok(KeyKind::Symmetric),
+            _ => anyhow::bail!("Unknown key algorithm: {}", s),
+        }
+    }
+}
+
+impl From<KeyKind> for &'static str {
+    fn from(kind: KeyKind) -> Self {
+        match kind {
+            KeyKind::Blake3 => "blake3",
+            KeyKind::Ed25519Secret => "ed25519-secret",
+            KeyKind::Ed25519Public => "ed25519-public",
+            KeyKind::Symmetric => "symmetric",
+        }
+    }
+}
+
+impl Display for KeyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+/// A key with the metadata needed to tell key files apart:
+///
+/// ```text
+/// -----BEGIN RCLI ED25519 PRIVATE KEY-----
+/// Version: 1
+/// Algorithm: ed25519-secret
+/// Key-Id: 3f0c1a9e5b7d2c41
+/// Created: 2026-10-19T12:00:00Z
+///
+/// <base64 key bytes>
+/// -----END RCLI ED25519 PRIVATE KEY-----
+/// ```
+#[derive(Debug, Clone, PartialEq, Zeroize, ZeroizeOnDrop)]
+pub struct KeyFile {
+    #[zeroize(skip)]
+    pub kind: KeyKind,
+    #[zeroize(skip)]
+    pub version: u32,
+    #[zeroize(skip)]
+    pub key_id: String,
+    #[zeroize(skip)]
+    pub created: DateTime<Utc>,
+    pub key: Vec<u8>,
+}
+
+impl KeyFile {
+    pub fn new(kind: KeyKind, key: Vec<u8>) -> anyhow::Result<Self> {
+        Ok(Self {
+            kind,
+            version: KEY_FILE_VERSION,
+            key_id: key_id(kind, &key)?,
+            created: Utc::now(),
+            key,
+        })
+    }
+
+    pub fn to_armored(&self) -> String {
+        let label = self.kind.label();
+        let body = base64_encode(Base64Format::Standard, &self.key);
+        let mut out = format!(
+            "{}{}-----\nVersion: {}\nAlgorithm: {}\nKey-Id: {}\nCreated: {}\n\n",
+            ARMOR_PREFIX,
+            label,
+            self.version,
+            self.kind,
+            self.key_id,
+            self.created.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        for line in body.as_bytes().chunks(ARMOR_WIDTH) {
+            out.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+            out.push('\n');
+        }
+        out.push_str(&format!("-----END RCLI {}-----\n", label));
+        out
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut lines = text.lines().map(str::trim_end);
+        let label = lines
+            .next()
+            .and_then(|line| line.strip_prefix(ARMOR_PREFIX))
+            .and_then(|line| line.strip_suffix("-----"))
+            .ok_or_else(|| anyhow::anyhow!("Not an rcli key file"))?;
+
+        let (mut version, mut kind, mut key_id, mut created) = (None, None, None, None);
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("Invalid key file header: {}", line))?;
+            let value = value.trim();
+            match name {
+                "Version" => version = Some(value.parse::<u32>()?),
+                "Algorithm" => kind = Some(value.parse::<KeyKind>()?),
+                "Key-Id" => key_id = Some(value.to_string()),
+                "Created" => created = Some(DateTime::parse_from_rfc3339(value)?.to_utc()),
+                // unknown headers are skipped so later versions can add some
+                _ => {}
+            }
+        }
+        let version = version.ok_or_else(|| anyhow::anyhow!("Key file has no Version"))?;
+        if version != KEY_FILE_VERSION {
+            anyhow::bail!("Unsupported key file version: {}", version);
+        }
+        let kind = kind.ok_or_else(|| anyhow::anyhow!("Key file has no Algorithm"))?;
+        if kind.label() != label {
+            anyhow::bail!("Key file label doesn't match its algorithm {}", kind);
+        }
+
+        let end = format!("-----END RCLI {}-----", label);
+        let body: String = lines.by_ref().take_while(|line| *line != end).collect();
+        let (key, _) = base64_decode_lenient(&body)?;
+        if key.len() != kind.key_len() {
+            anyhow::bail!("{} key must be {} bytes", kind, kind.key_len());
+        }
+        let key_file = Self {
+            kind,
+            version,
+            key_id: key_id.ok_or_else(|| anyhow::anyhow!("Key file has no Key-Id"))?,
+            created: created.ok_or_else(|| anyhow::anyhow!("Key file has no Created"))?,
+            key,
+        };
+        if key_file.key_id != self::key_id(kind, &key_file.key)? {
+            anyhow::bail!("Key-Id doesn't match the key, the file may be corrupted");
+        }
+        Ok(key_file)
+    }
+}
+
+/// Short fingerprint of a key. Both halves of an Ed25519 pair share the id
+/// of the public key, secret keys are never exposed by it.
+pub fn key_id(kind: KeyKind, key: &[u8]) -> anyhow::Result<String> {
+    let public = match kind {
+        KeyKind::Blake3 | KeyKind::Symmetric | KeyKind::Ed25519Public => key.to_vec(),
+        KeyKind::Ed25519Secret => SigningKey::from_bytes(key.try_into()?)
+            .verifying_key()
+            .to_bytes()
+            .to_vec(),
+    };
+    let mut hasher = blake3::Hasher::new_derive_key(KEY_ID_CONTEXT);
+    hasher.update(match kind {
+        KeyKind::Blake3 => b"blake3".as_slice(),
+        KeyKind::Symmetric => b"symmetric",
+        KeyKind::Ed25519Secret | KeyKind::Ed25519Public => b"ed25519",
+    });
+    hasher.update(&public);
+    Ok(data_encoding::HEXLOWER.encode(&hasher.finalize().as_bytes()[..8]))
+}
+
+/// Reads the key bytes of `kind` from an armored key file, or from a legacy
+/// file holding nothing but the raw key.
+pub fn read_key(path: impl AsRef<Path>, kind: KeyKind) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let data = Zeroizing::new(fs::read(path)?);
+    if data.starts_with(ARMOR_PREFIX.as_bytes()) {
+        let mut key_file = KeyFile::parse(std::str::from_utf8(&data)?)?;
+        if key_file.kind != kind {
+            anyhow::bail!("Expected a {} key, found {}", kind, key_file.kind);
+        }
+        return Ok(Zeroizing::new(std::mem::take(&mut key_file.key)));
+    }
+    let format = detect_key_format(&data);
+    if format != KeyFormat::Raw {
+        anyhow::bail!("Expected a {} key, found an Ed25519 {} key", kind, format);
+    }
+    // legacy blake3 keys were printable text followed by a newline
+    let legacy =
+        kind == KeyKind::Blake3 && data.len() == kind.key_len() + 1 && data.ends_with(b"\n");
+    if data.len() != kind.key_len() && !legacy {
+        anyhow::bail!(
+            "{} key must be {} bytes, got {}",
+            kind,
+            kind.key_len(),
+            data.len()
+        );
+    }
+    Ok(Zeroizing::new(data[..kind.key_len()].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_file_round_trip() -> anyhow::Result<()> {
+        let sk = fs::read("fixtures/ed25519.sk")?;
+        let pk = fs::read("fixtures/ed25519.pk")?;
+        let secret = KeyFile::new(KeyKind::Ed25519Secret, sk)?;
+        let public = KeyFile::new(KeyKind::Ed25519Public, pk)?;
+        assert_eq!(secret.key_id, public.key_id);
+        assert_eq!(secret.key_id.len(), 16);
+
+        let armored = secret.to_armored();
+        assert!(armored.starts_with("-----BEGIN RCLI ED25519 PRIVATE KEY-----\nVersion: 1\n"));
+        let mut parsed = KeyFile::parse(&armored)?;
+        assert_eq!(parsed.created.timestamp(), secret.created.timestamp());
+        parsed.created = secret.created;
+        assert_eq!(parsed, secret);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_file_rejects_tampering() -> anyhow::Result<()> {
+        let armored = KeyFile::new(KeyKind::Blake3, vec![7; 32])?.to_armored();
+        assert!(KeyFile::parse(&armored.replace("Version: 1", "Version: 2")).is_err());
+        assert!(KeyFile::parse(&armored.replace("BLAKE3 KEY", "ED25519 PUBLIC KEY")).is_err());
+        let body = base64_encode(Base64Format::Standard, &[7; 32]);
+        let other = base64_encode(Base64Format::Standard, &[8; 32]);
+        assert!(KeyFile::parse(&armored.replace(&body, &other)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_legacy_keys() -> anyhow::Result<()> {
+        assert_eq!(
+            *read_key("fixtures/blake3.txt", KeyKind::Blake3)?,
+            b"0]7l$TJk8NNJlwlJ=Gmu#MTWYx{X}kZj"
+        );
+        assert_eq!(
+            *read_key("fixtures/ed25519.sk", KeyKind::Ed25519Secret)?,
+            fs::read("fixtures/ed25519.sk")?
+        );
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("blake3.key");
+        fs::write(
+            &path,
+            KeyFile::new(KeyKind::Blake3, vec![1; 32])?.to_armored(),
+        )?;
+        assert_eq!(*read_key(&path, KeyKind::Blake3)?, [1; 32]);
+        assert!(read_key(&path, KeyKind::Ed25519Public).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_key_rejects_other_encodings() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("key");
+        let pk = crate::load_ed25519_key("fixtures/ed25519.pk", KeyKind::Ed25519Public)?;
+        for format in [KeyFormat::Pem, KeyFormat::OpenSsh, KeyFormat::Jwk] {
+            fs::write(&path, crate::encode_ed25519_key(&pk, format)?)?;
+            assert!(read_key(&path, KeyKind::Blake3).is_err(), "{}", format);
+        }
+        // trailing bytes used to be dropped
+        fs::write(&path, [1; 64])?;
+        assert!(read_key(&path, KeyKind::Blake3).is_err());
+        fs::write(&path, [1; 31])?;
+        assert!(read_key(&path, KeyKind::Blake3).is_err());
+        // only legacy BLAKE3 keys had a newline
+        fs::write(&path, [[1; 32].as_slice(), b"\n"].concat())?;
+        assert_eq!(*read_key(&path, KeyKind::Blake3)?, [1; 32]);
+        assert!(read_key(&path, KeyKind::Symmetric).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{TextSignTreeOpts, TextVerifyTreeOpts, sign_reader, verify_reader};
+
+pub const MANIFEST_FILE: &str = "rcli-manifest.txt";
+const MANIFEST_HEADER: &str = "rcli-manifest v1";
+const SIGNATURE_PREFIX: &str = "signature: ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    /// Relative to the signed directory, always with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub hash: blake3::Hash,
+}
+
+/// Every file under a directory, sorted by path. The canonical text form is
+/// what gets signed:
+///
+/// ```text
+/// rcli-manifest v1
+/// <blake3 hex>  <size>  <path>
+/// signature: ed25519:<base64>
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TreeReport {
+    pub signature_valid: bool,
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl TreeReport {
+    pub fn is_ok(&self) -> bool {
+        self.signature_valid
+            && self.modified.is_empty()
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+    }
+}
+
+impl Display for TreeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.signature_valid {
+            writeln!(f, "Manifest signature is INVALID")?;
+        }
+        for (label, paths) in [
+            ("modified", &self.modified),
+            ("missing", &self.missing),
+            ("extra", &self.extra),
+        ] {
+            for path in paths {
+                writeln!(f, "{:<9} {}", label, path)?;
+            }
+        }
+        if self.is_ok() {
+            write!(f, "All files match the signed manifest")
+        } else {
+            write!(
+                f,
+                "{} modified, {} missing, {} extra",
+                self.modified.len(),
+                self.missing.len(),
+                self.extra.len()
+            )
+        }
+    }
+}
+
+impl Manifest {
+    /// Hashes every file under `dir` except `exclude`, which is where the
+    /// manifest itself lives.
+    pub fn scan(dir: &Path, exclude: Option<&Path>) -> anyhow::Result<Self> {
+        let mut entries = Vec::new();
+        scan_dir(dir, dir, exclude, &mut entries)?;
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { entries })
+    }
+
+    pub fn to_canonical(&self) -> String {
+        let mut out = format!("{}\n", MANIFEST_HEADER);
+        for entry in &self.entries {
+            out.push_str(&format!(
+                "{}  {}  {}\n",
+                entry.hash.to_hex(),
+                entry.size,
+                entry.path
+            ));
+        }
+        out
+    }
+
+    /// Returns the manifest, the exact text that was signed and the signature.
+    pub fn parse(text: &str) -> anyhow::Result<(Self, &str, &str)> {
+        let start = text
+            .rfind(&format!("\n{}", SIGNATURE_PREFIX))
+            .ok_or_else(|| anyhow::anyhow!("Manifest isn't signed"))?;
+        let (signed, signature) = text.split_at(start + 1);
+        let signature = signature[SIGNATURE_PREFIX.len()..].trim();
+
+        let mut lines = signed.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            anyhow::bail!("Not an rcli manifest");
+        }
+        let entries = lines
+            .map(|line| {
+                let mut fields = line.splitn(3, "  ");
+                let (Some(hash), Some(size), Some(path)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    anyhow::bail!("Invalid manifest line: {}", line);
+                };
+                Ok(ManifestEntry {
+                    path: path.to_string(),
+                    size: size.parse()?,
+                    hash: blake3::Hash::from_hex(hash)?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok((Self { entries }, signed, signature))
+    }
+}
+
+fn scan_dir(
+    root: &Path,
+    dir: &Path,
+    exclude: Option<&Path>,
+    entries: &mut Vec<ManifestEntry>,
+) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_type = fs::symlink_metadata(&path)?.file_type();
+        if file_type.is_symlink() {
+            anyhow::bail!("Symlinks aren't supported: {}", path.display());
+        }
+        if file_type.is_dir() {
+            scan_dir(root, &path, exclude, entries)?;
+            continue;
+        }
+        if exclude.is_some_and(|exclude| exclude == path) {
+            continue;
+        }
+        let relative = path
+            .strip_prefix(root)?
+            .iter()
+            .map(|part| {
+                part.to_str()
+                    .filter(|part| !part.contains(['\n', '\r']))
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported file name: {}", path.display()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .join("/");
+        let mut hasher = blake3::Hasher::new();
+        let size = io::copy(&mut File::open(&path)?, &mut hasher)?;
+        entries.push(ManifestEntry {
+            path: relative,
+            size,
+            hash: hasher.finalize(),
+        });
+    }
+    Ok(())
+}
+
+fn manifest_path(dir: &Path, manifest: Option<&PathBuf>) -> PathBuf {
+    manifest.cloned().unwrap_or_else(|| dir.join(MANIFEST_FILE))
+}
+
+// Compared against paths found by walking `dir`, so both have to be spelled
+// the same way.
+fn exclude_path(dir: &Path, manifest: &Path) -> anyhow::Result<PathBuf> {
+    let parent = match manifest.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let relative = fs::canonicalize(parent)?
+        .join(manifest.file_name().unwrap_or_default())
+        .strip_prefix(fs::canonicalize(dir)?)
+        .map(Path::to_path_buf);
+    Ok(match relative {
+        Ok(relative) => dir.join(relative),
+        Err(_) => manifest.to_path_buf(),
+    })
+}
+
+/// Writes the signed manifest and returns where it went.
+pub fn process_text_sign_tree(opts: TextSignTreeOpts) -> anyhow::Result<(PathBuf, Manifest)> {
+    let path = manifest_path(&opts.dir, opts.manifest.as_ref());
+    let manifest = Manifest::scan(&opts.dir, Some(&exclude_path(&opts.dir, &path)?))?;
+    let canonical = manifest.to_canonical();
+    let signature = sign_reader(opts.format, &opts.key, &mut canonical.as_bytes())?;
+    fs::write(
+        &path,
+        format!("{}{}{}\n", canonical, SIGNATURE_PREFIX, signature),
+    )?;
+    Ok((path, manifest))
+}
+
+pub fn process_text_verify_tree(opts: TextVerifyTreeOpts) -> anyhow::Result<TreeReport> {
+    let path = manifest_path(&opts.dir, opts.manifest.as_ref());
+    let text = fs::read_to_string(&path)?;
+    let (signed, signed_text, signature) = Manifest::parse(&text)?;
+    let signature_valid = verify_reader(
+        opts.format,
+        &opts.key,
+        &mut signed_text.as_bytes(),
+        signature,
+    )?;
+
+    let actual = Manifest::scan(&opts.dir, Some(&exclude_path(&opts.dir, &path)?))?;
+    let mut actual: BTreeMap<_, _> = actual
+        .entries
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+    let mut report = TreeReport {
+        signature_valid,
+        ..Default::default()
+    };
+    for entry in signed.entries {
+        match actual.remove(&entry.path) {
+            Some(found) if found == entry => {}
+            Some(_) => report.modified.push(entry.path),
+            None => report.missing.push(entry.path),
+        }
+    }
+    report.extra = actual.into_keys().collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TextSignFormat;
+
+    fn sign(dir: &Path, format: TextSignFormat, key: &str) -> anyhow::Result<()> {
+        process_text_sign_tree(TextSignTreeOpts {
+            dir: dir.to_path_buf(),
+            key: key.to_string(),
+            format,
+            manifest: None,
+        })?;
+        Ok(())
+    }
+
+    fn verify(dir: &Path, key: &str) -> anyhow::Result<TreeReport> {
+        process_text_verify_tree(TextVerifyTreeOpts {
+            dir: dir.to_path_buf(),
+            key: key.to_string(),
+            manifest: None,
+            format: None,
+        })
+    }
+
+    #[test]
+    fn test_sign_and_verify_tree() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("assets/img"))?;
+        fs::write(dir.join("index.html"), "<h1>rcli</h1>")?;
+        fs::write(dir.join("assets/app.js"), "console.log(1)")?;
+        fs::write(dir.join("assets/img/logo.png"), [0x89, b'P', b'N', b'G'])?;
+
+        sign(dir, TextSignFormat::Ed25519, "fixtures/ed25519.sk")?;
+        let text = fs::read_to_string(dir.join(MANIFEST_FILE))?;
+        let (manifest, _, signature) = Manifest::parse(&text)?;
+        let paths: Vec<_> = manifest.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["assets/app.js", "assets/img/logo.png", "index.html"]
+        );
+        assert!(signature.starts_with("ed25519:"));
+        assert!(verify(dir, "fixtures/ed25519.pk")?.is_ok());
+
+        fs::write(dir.join("index.html"), "<h1>tampered</h1>")?;
+        fs::remove_file(dir.join("assets/app.js"))?;
+        fs::write(dir.join("assets/extra.js"), "")?;
+        let report = verify(dir, "fixtures/ed25519.pk")?;
+        assert_eq!(
+            report,
+            TreeReport {
+                signature_valid: true,
+                modified: vec!["index.html".to_string()],
+                missing: vec!["assets/app.js".to_string()],
+                extra: vec!["assets/extra.js".to_string()],
+            }
+        );
+
+        // editing the manifest to match breaks its signature
+        let forged = text.replace(
+            &format!("{}  13", blake3::hash(b"<h1>rcli</h1>").to_hex()),
+            &format!("{}  17", blake3::hash(b"<h1>tampered</h1>").to_hex()),
+        );
+        assert_ne!(forged, text);
+        fs::write(dir.join(MANIFEST_FILE), forged)?;
+        let report = verify(dir, "fixtures/ed25519.pk")?;
+        assert!(!report.signature_valid);
+        assert!(report.modified.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_blake3_tree_signature() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        fs::write(dir.join("a.txt"), "a")?;
+        sign(dir, TextSignFormat::Blake3, "fixtures/blake3.txt")?;
+        assert!(verify(dir, "fixtures/blake3.txt")?.is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_key_mac_cannot_resign_tree() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path();
+        fs::write(dir.join("a.txt"), "a")?;
+        sign(dir, TextSignFormat::Ed25519, "fixtures/ed25519.sk")?;
+
+        // anyone can key a BLAKE3 MAC with the public key and tag it blake3:
+        fs::write(dir.join("a.txt"), "tampered")?;
+        sign(dir, TextSignFormat::Blake3, "fixtures/ed25519.pk")?;
+        assert!(verify(dir, "fixtures/ed25519.pk").is_err());
+        Ok(())
+    }
+}
@@ -1,11 +1,35 @@
+mod age;
 mod base64;
+mod breach;
+mod codec;
+mod compress;
 mod csv;
+mod datauri;
+mod encrypt;
 mod http;
+mod keyfile;
+mod keyformat;
+mod manifest;
+mod otp;
 mod pwd;
+mod pwd_hash;
+mod random;
 mod text;
 
+pub use age::*;
 pub use base64::*;
+pub use breach::BreachDb;
+pub use codec::*;
+pub use compress::*;
 pub use csv::process_csv;
+pub use datauri::*;
+pub use encrypt::*;
 pub use http::*;
-pub use pwd::process_pwd;
+pub use keyfile::*;
+pub use keyformat::*;
+pub use manifest::*;
+pub use otp::*;
+pub use pwd::*;
+pub use pwd_hash::*;
+pub use random::*;
 pub use text::*;
@@ -0,0 +1,363 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    OtpAlgorithm, OtpGenerateOpts, OtpHotpOpts, OtpParams, OtpTotpOpts, OtpVerifyOpts, read_secret,
+};
+
+// RFC 3986 unreserved characters plus '@', which is common in account names
+const URI_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'@');
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OtpKind {
+    Totp,
+    Hotp,
+}
+
+/// Everything needed to compute codes, as found in an `otpauth://` URI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtpConfig {
+    pub kind: OtpKind,
+    pub secret: Vec<u8>,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    pub period: u64,
+    pub counter: u64,
+    pub issuer: Option<String>,
+    pub account: Option<String>,
+}
+
+impl OtpConfig {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            kind: OtpKind::Totp,
+            secret,
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            period: 30,
+            counter: 0,
+            issuer: None,
+            account: None,
+        }
+    }
+
+    /// Accepts either a bare base32 secret or an `otpauth://` URI.
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let input = input.trim();
+        if !input.starts_with("otpauth://") {
+            return Ok(Self::new(decode_secret(input)?));
+        }
+
+        let rest = &input["otpauth://".len()..];
+        let (kind, rest) = rest
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("Missing label in otpauth URI"))?;
+        let kind = match kind.to_lowercase().as_str() {
+            "totp" => OtpKind::Totp,
+            "hotp" => OtpKind::Hotp,
+            v => anyhow::bail!("Invalid otpauth type: {}", v),
+        };
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let label = percent_decode_str(label).decode_utf8()?;
+        let (mut issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(issuer.to_string()), account.trim().to_string()),
+            None => (None, label.to_string()),
+        };
+
+        let mut secret = None;
+        let mut config = Self::new(Vec::new());
+        config.kind = kind;
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode_str(value).decode_utf8()?;
+            match key {
+                "secret" => secret = Some(decode_secret(&value)?),
+                "issuer" => issuer = Some(value.to_string()),
+                "algorithm" => config.algorithm = value.parse()?,
+                "digits" => config.digits = value.parse()?,
+                "period" => config.period = value.parse()?,
+                "counter" => config.counter = value.parse()?,
+                // image, color, ... are for authenticator apps only
+                _ => {}
+            }
+        }
+        config.secret = secret.ok_or_else(|| anyhow::anyhow!("Missing secret in otpauth URI"))?;
+        config.issuer = issuer;
+        config.account = (!account.is_empty()).then_some(account);
+        Ok(config)
+    }
+
+    pub fn to_uri(&self) -> String {
+        let encode = |s: &str| utf8_percent_encode(s, URI_ENCODE).to_string();
+        let label = encode(self.account.as_deref().unwrap_or_default());
+        let kind = match self.kind {
+            OtpKind::Totp => "totp",
+            OtpKind::Hotp => "hotp",
+        };
+        let mut uri = match &self.issuer {
+            Some(issuer) => format!("otpauth://{}/{}:{}?", kind, encode(issuer), label),
+            None => format!("otpauth://{}/{}?", kind, label),
+        };
+        uri.push_str(&format!("secret={}", BASE32_NOPAD.encode(&self.secret)));
+        if let Some(issuer) = &self.issuer {
+            uri.push_str(&format!("&issuer={}", encode(issuer)));
+        }
+        uri.push_str(&format!(
+            "&algorithm={}&digits={}",
+            self.algorithm, self.digits
+        ));
+        match self.kind {
+            OtpKind::Totp => uri.push_str(&format!("&period={}", self.period)),
+            OtpKind::Hotp => uri.push_str(&format!("&counter={}", self.counter)),
+        }
+        uri
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if !(6..=8).contains(&self.digits) {
+            anyhow::bail!("Digits must be between 6 and 8, got {}", self.digits);
+        }
+        if self.period == 0 {
+            anyhow::bail!("Period must be greater than 0");
+        }
+        if self.secret.is_empty() {
+            anyhow::bail!("Secret must not be empty");
+        }
+        Ok(())
+    }
+}
+
+/// RFC 4226 HOTP value for `counter`, zero padded to `digits`.
+pub fn hotp(secret: &[u8], counter: u64, digits: u32, algorithm: OtpAlgorithm) -> String {
+    let mac = hmac(algorithm, secret, &counter.to_be_bytes());
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes(
+        mac[offset..offset + 4]
+            .try_into()
+            .expect("offset leaves 4 bytes"),
+    ) & 0x7fff_ffff;
+    let code = binary % 10u32.pow(digits);
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// RFC 6238 TOTP value for the unix `time`.
+pub fn totp(secret: &[u8], time: u64, period: u64, digits: u32, algorithm: OtpAlgorithm) -> String {
+    hotp(secret, time / period, digits, algorithm)
+}
+
+fn hmac(algorithm: OtpAlgorithm, key: &[u8], msg: &[u8]) -> Vec<u8> {
+    const ANY_KEY: &str = "HMAC accepts keys of any length";
+    match algorithm {
+        OtpAlgorithm::Sha1 => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(key).expect(ANY_KEY);
+            mac.update(msg);
+            mac.finalize().into_bytes().to_vec()
+        }
+        OtpAlgorithm::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).expect(ANY_KEY);
+            mac.update(msg);
+            mac.finalize().into_bytes().to_vec()
+        }
+        OtpAlgorithm::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(key).expect(ANY_KEY);
+            mac.update(msg);
+            mac.finalize().into_bytes().to_vec()
+        }
+    }
+}
+
+// Authenticator apps show secrets grouped, lowercased and sometimes padded.
+fn decode_secret(secret: &str) -> anyhow::Result<Vec<u8>> {
+    let secret: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    Ok(BASE32_NOPAD.decode(secret.as_bytes())?)
+}
+
+fn load_config(params: &OtpParams, period: Option<u64>) -> anyhow::Result<OtpConfig> {
+    let secret = read_secret(&params.input, "Secret: ")?;
+    let mut config = OtpConfig::parse(&secret)?;
+    if let Some(algorithm) = params.algorithm {
+        config.algorithm = algorithm;
+    }
+    if let Some(digits) = params.digits {
+        config.digits = digits;
+    }
+    if let Some(period) = period {
+        config.period = period;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+fn now() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+pub fn process_otp_totp(opts: OtpTotpOpts) -> anyhow::Result<String> {
+    let config = load_config(&opts.params, opts.period)?;
+    let time = opts.time.map_or_else(now, Ok)?;
+    Ok(totp(
+        &config.secret,
+        time,
+        config.period,
+        config.digits,
+        config.algorithm,
+    ))
+}
+
+pub fn process_otp_hotp(opts: OtpHotpOpts) -> anyhow::Result<String> {
+    let config = load_config(&opts.params, None)?;
+    let counter = opts.counter.unwrap_or(config.counter);
+    Ok(hotp(
+        &config.secret,
+        counter,
+        config.digits,
+        config.algorithm,
+    ))
+}
+
+/// Returns the offset of the matching code from the expected counter (time
+/// step for TOTP), or `None` if no code within the window matches.
+pub fn process_otp_verify(opts: OtpVerifyOpts) -> anyhow::Result<Option<i64>> {
+    let config = load_config(&opts.params, opts.period)?;
+    let code = opts.code.trim();
+    let window = opts.window as i64;
+
+    let (base, offsets) = match (opts.counter, config.kind) {
+        (Some(counter), _) => (counter, 0..=window),
+        (None, OtpKind::Hotp) => (config.counter, 0..=window),
+        (None, OtpKind::Totp) => {
+            let time = opts.time.map_or_else(now, Ok)?;
+            (time / config.period, -window..=window)
+        }
+    };
+    for offset in offsets {
+        let Some(counter) = base.checked_add_signed(offset) else {
+            continue;
+        };
+        let expected = hotp(&config.secret, counter, config.digits, config.algorithm);
+        // constant time, so response timing doesn't reveal matching digits
+        if bool::from(expected.as_bytes().ct_eq(code.as_bytes())) {
+            return Ok(Some(offset));
+        }
+    }
+    Ok(None)
+}
+
+/// A fresh random secret from the OS CSPRNG, as base32 and `otpauth://` URI.
+pub fn process_otp_generate(opts: OtpGenerateOpts) -> anyhow::Result<(String, String)> {
+    if opts.bytes < 16 {
+        anyhow::bail!("Secrets must be at least 16 bytes (128 bits)");
+    }
+    let mut secret = vec![0u8; opts.bytes];
+    OsRng.fill_bytes(&mut secret);
+
+    let mut config = OtpConfig::new(secret);
+    config.algorithm = opts.algorithm;
+    config.digits = opts.digits;
+    config.period = opts.period;
+    config.issuer = opts.issuer;
+    config.account = Some(opts.account);
+    config.validate()?;
+    Ok((BASE32_NOPAD.encode(&config.secret), config.to_uri()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_hotp_rfc4226_vectors() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(
+                hotp(RFC_SECRET, counter as u64, 6, OtpAlgorithm::Sha1),
+                *code
+            );
+        }
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let sha256_secret = b"12345678901234567890123456789012";
+        let sha512_secret = b"1234567890123456789012345678901234567890123456789012345678901234";
+        for (time, sha1, sha256, sha512) in [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (2000000000, "69279037", "90698825", "38618901"),
+        ] {
+            assert_eq!(totp(RFC_SECRET, time, 30, 8, OtpAlgorithm::Sha1), sha1);
+            assert_eq!(
+                totp(sha256_secret, time, 30, 8, OtpAlgorithm::Sha256),
+                sha256
+            );
+            assert_eq!(
+                totp(sha512_secret, time, 30, 8, OtpAlgorithm::Sha512),
+                sha512
+            );
+        }
+    }
+
+    #[test]
+    fn test_otpauth_uri_round_trip() -> anyhow::Result<()> {
+        let uri = "otpauth://totp/ACME%20Co:john.doe@example.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60";
+        let config = OtpConfig::parse(uri)?;
+        assert_eq!(config.kind, OtpKind::Totp);
+        assert_eq!(config.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(config.account.as_deref(), Some("john.doe@example.com"));
+        assert_eq!(config.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!((config.digits, config.period), (8, 60));
+        assert_eq!(OtpConfig::parse(&config.to_uri())?, config);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_secret_is_lenient() -> anyhow::Result<()> {
+        let config = OtpConfig::parse("gezd gnbv gy3t qojq gezd gnbv gy3t qojq")?;
+        assert_eq!(config.secret, RFC_SECRET);
+        Ok(())
+    }
+
+    #[test]
+    fn test_otp_verify_window() -> anyhow::Result<()> {
+        let verify = |code: &str, time: u64| {
+            process_otp_verify(OtpVerifyOpts {
+                params: OtpParams {
+                    input: "fixtures/otp.txt".to_string(),
+                    algorithm: None,
+                    digits: Some(8),
+                },
+                code: code.to_string(),
+                period: None,
+                time: Some(time),
+                counter: None,
+                window: 1,
+            })
+        };
+        assert_eq!(verify("07081804", 1111111109)?, Some(0));
+        assert_eq!(verify("07081804", 1111111109 + 30)?, Some(-1));
+        assert_eq!(verify("07081804", 1111111109 + 60)?, None);
+        Ok(())
+    }
+}
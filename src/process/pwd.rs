@@ -1,62 +1,751 @@
-use crate::opt::PwdOpts;
-use rand::seq::SliceRandom;
+use std::fmt::Display;
+use std::fs;
+use std::io::{BufRead, BufReader};
+
+use crate::opt::{PwdCheckOpts, PwdDeriveOpts, PwdOpts};
+use crate::{BreachDb, get_reader};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use zeroize::Zeroizing;
+use zxcvbn::matching::patterns::MatchPattern;
+use zxcvbn::time_estimates::CrackTimeSeconds;
+use zxcvbn::zxcvbn;
 
 const UPPERCASE_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 const LOWERCASE_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
 const NUMBER_CHARS: &[u8] = b"0123456789";
 const SYMBOL_CHARS: &[u8] = b"!@#$%^&*()-_=+[]{}|;:,.<>?";
+// no c/q/x: they are easily misheard or spelled out differently
+const CONSONANT_CHARS: &[u8] = b"bdfghjklmnprstvwz";
+const VOWEL_CHARS: &[u8] = b"aeiou";
+
+// How many breached passwords we regenerate before giving up; with sane
+// options even a single hit is astronomically unlikely.
+const MAX_BREACH_RETRIES: usize = 16;
 
 pub fn process_pwd(opts: PwdOpts) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
+    match opts.seed {
+        Some(seed) => process_pwd_with_rng(opts, &mut ChaCha20Rng::seed_from_u64(seed)),
+        None => process_pwd_with_rng(opts, &mut rand::thread_rng()),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PwdEntry {
+    pub password: String,
+    pub length: usize,
+    pub classes: Vec<&'static str>,
+    pub entropy_bits: f64,
+    pub score: u8,
+}
+
+pub fn process_pwd_batch(opts: PwdOpts, count: usize) -> anyhow::Result<Vec<PwdEntry>> {
+    match opts.seed {
+        Some(seed) => {
+            process_pwd_batch_with_rng(opts, count, &mut ChaCha20Rng::seed_from_u64(seed))
+        }
+        None => process_pwd_batch_with_rng(opts, count, &mut rand::thread_rng()),
+    }
+}
+
+pub fn process_pwd_batch_with_rng<R: RngCore + CryptoRng + ?Sized>(
+    opts: PwdOpts,
+    count: usize,
+    rng: &mut R,
+) -> anyhow::Result<Vec<PwdEntry>> {
+    let entropy_bits = pwd_entropy_bits(&opts)?;
+    let mut db = opts.breach_db.as_ref().map(BreachDb::open).transpose()?;
+    (0..count)
+        .map(|_| {
+            let password = generate_unbreached(&opts, db.as_mut(), rng)?;
+            let classes = [
+                ("uppercase", UPPERCASE_CHARS),
+                ("lowercase", LOWERCASE_CHARS),
+                ("numbers", NUMBER_CHARS),
+                ("symbols", SYMBOL_CHARS),
+            ]
+            .into_iter()
+            .filter(|(_, chars)| password.bytes().any(|c| chars.contains(&c)))
+            .map(|(name, _)| name)
+            .collect();
+            Ok(PwdEntry {
+                length: password.len(),
+                classes,
+                entropy_bits,
+                score: zxcvbn(&password, &[]).score().into(),
+                password,
+            })
+        })
+        .collect()
+}
+
+pub fn process_pwd_with_rng<R: RngCore + CryptoRng + ?Sized>(
+    opts: PwdOpts,
+    rng: &mut R,
+) -> anyhow::Result<String> {
+    let mut db = opts.breach_db.as_ref().map(BreachDb::open).transpose()?;
+    generate_unbreached(&opts, db.as_mut(), rng)
+}
+
+fn generate_unbreached<R: RngCore + ?Sized>(
+    opts: &PwdOpts,
+    mut db: Option<&mut BreachDb>,
+    rng: &mut R,
+) -> anyhow::Result<String> {
+    for _ in 0..MAX_BREACH_RETRIES {
+        let password = generate_pwd(opts, rng)?;
+        let breached = match db.as_deref_mut() {
+            Some(db) => db.lookup(&password)? > 0,
+            None => false,
+        };
+        if !breached {
+            return Ok(password);
+        }
+    }
+    anyhow::bail!("Every generated password was found in the breach database, try a longer length")
+}
+
+fn generate_pwd<R: RngCore + ?Sized>(opts: &PwdOpts, rng: &mut R) -> anyhow::Result<String> {
+    if opts.pronounceable {
+        return generate_pronounceable(opts, rng);
+    }
+    let classes = [opts.uppercase, opts.lowercase, opts.numbers, opts.symbols];
+    let required = classes.iter().filter(|&&c| c).count() as u8;
+    if required == 0 {
+        anyhow::bail!("At least one character class must be enabled");
+    }
+    if opts.length < required {
+        anyhow::bail!(
+            "Length must be at least {} for the enabled classes",
+            required
+        );
+    }
+
     let mut password = Vec::new();
     let mut chars = Vec::new();
+    for (enabled, set) in [
+        (opts.uppercase, UPPERCASE_CHARS),
+        (opts.lowercase, LOWERCASE_CHARS),
+        (opts.numbers, NUMBER_CHARS),
+        (opts.symbols, SYMBOL_CHARS),
+    ] {
+        if enabled {
+            chars.extend_from_slice(set);
+            password.push(pick(rng, set));
+        }
+    }
 
-    if opts.uppercase {
-        chars.extend_from_slice(UPPERCASE_CHARS);
-        password.push(
-            *UPPERCASE_CHARS
-                .choose(&mut rng)
-                .expect("UPPERCASE won't be empty"),
-        );
+    for _ in 0..(opts.length - password.len() as u8) {
+        password.push(pick(rng, &chars));
+    }
+
+    shuffle(rng, &mut password);
+
+    let password = String::from_utf8(password)?;
+
+    Ok(password)
+}
+
+// Alternating consonant/vowel syllables that are easy to read out loud, one
+// capital letter if uppercase is requested, then digits and a symbol at the
+// end so the letters stay one speakable word.
+fn generate_pronounceable<R: RngCore + ?Sized>(
+    opts: &PwdOpts,
+    rng: &mut R,
+) -> anyhow::Result<String> {
+    let (letters, digits, symbols) = pronounceable_layout(opts)?;
+
+    let mut password = Vec::with_capacity(opts.length as usize);
+    for i in 0..letters {
+        let set = if i % 2 == 0 {
+            CONSONANT_CHARS
+        } else {
+            VOWEL_CHARS
+        };
+        password.push(pick(rng, set));
+    }
+    if !opts.lowercase {
+        password.make_ascii_uppercase();
+    } else if opts.uppercase {
+        let i = below(rng, letters);
+        password[i].make_ascii_uppercase();
+    }
+    for _ in 0..digits {
+        password.push(pick(rng, NUMBER_CHARS));
     }
+    for _ in 0..symbols {
+        password.push(pick(rng, SYMBOL_CHARS));
+    }
+
+    Ok(String::from_utf8(password)?)
+}
+
+// Uniform index in 0..n from whole u32 draws: draws at or above the largest
+// multiple of n are rejected, so there is no modulo bias. Only `next_u32` is
+// used, so derived passwords don't depend on how the rand crate samples.
+fn below<R: RngCore + ?Sized>(rng: &mut R, n: usize) -> usize {
+    let n = u32::try_from(n).expect("sets are small");
+    assert!(n > 0, "can't sample from an empty set");
+    let limit = u32::MAX - u32::MAX % n;
+    loop {
+        let x = rng.next_u32();
+        if x < limit {
+            return (x % n) as usize;
+        }
+    }
+}
 
-    if opts.lowercase {
-        chars.extend_from_slice(LOWERCASE_CHARS);
-        password.push(
-            *LOWERCASE_CHARS
-                .choose(&mut rng)
-                .expect("LOWERCASE won't be empty"),
+fn pick<R: RngCore + ?Sized>(rng: &mut R, set: &[u8]) -> u8 {
+    set[below(rng, set.len())]
+}
+
+// Fisher-Yates, from the last position down
+fn shuffle<R: RngCore + ?Sized>(rng: &mut R, bytes: &mut [u8]) {
+    for i in (1..bytes.len()).rev() {
+        bytes.swap(i, below(rng, i + 1));
+    }
+}
+
+// (letters, digits, symbols) for a pronounceable password of `opts.length`
+fn pronounceable_layout(opts: &PwdOpts) -> anyhow::Result<(usize, usize, usize)> {
+    if !opts.uppercase && !opts.lowercase {
+        anyhow::bail!("Pronounceable passwords need uppercase or lowercase letters");
+    }
+    let digits = if opts.numbers { 2 } else { 0 };
+    let symbols = if opts.symbols { 1 } else { 0 };
+    let length = opts.length as usize;
+    if length < digits + symbols + 2 {
+        anyhow::bail!(
+            "Length must be at least {} for a pronounceable password",
+            digits + symbols + 2
         );
     }
+    Ok((length - digits - symbols, digits, symbols))
+}
 
-    if opts.numbers {
-        chars.extend_from_slice(NUMBER_CHARS);
-        password.push(
-            *NUMBER_CHARS
-                .choose(&mut rng)
-                .expect("NUMBER won't be empty"),
+/// Entropy in bits of a password generated with `opts`, assuming the
+/// attacker knows the generator and its options. Never more than the
+/// password really has.
+pub fn pwd_entropy_bits(opts: &PwdOpts) -> anyhow::Result<f64> {
+    let log2 = |n: usize| (n as f64).log2();
+    if opts.pronounceable {
+        let (letters, digits, symbols) = pronounceable_layout(opts)?;
+        let consonants = letters.div_ceil(2);
+        let mut bits = consonants as f64 * log2(CONSONANT_CHARS.len())
+            + (letters - consonants) as f64 * log2(VOWEL_CHARS.len())
+            + digits as f64 * log2(NUMBER_CHARS.len())
+            + symbols as f64 * log2(SYMBOL_CHARS.len());
+        if opts.uppercase && opts.lowercase {
+            bits += log2(letters);
+        }
+        return Ok(bits);
+    }
+
+    let classes: Vec<usize> = [
+        (opts.uppercase, UPPERCASE_CHARS),
+        (opts.lowercase, LOWERCASE_CHARS),
+        (opts.numbers, NUMBER_CHARS),
+        (opts.symbols, SYMBOL_CHARS),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, chars)| chars.len())
+    .collect();
+    if classes.is_empty() {
+        anyhow::bail!("At least one character class must be enabled");
+    }
+    if (opts.length as usize) < classes.len() {
+        anyhow::bail!(
+            "Length must be at least {} for the enabled classes",
+            classes.len()
         );
     }
+    // One character per class comes from that class alone, the rest from the
+    // whole charset. The shuffle adds a little on top that isn't counted, so
+    // this is a lower bound.
+    let charset: usize = classes.iter().sum();
+    let forced: f64 = classes.iter().map(|&n| log2(n)).sum();
+    Ok(forced + (opts.length as usize - classes.len()) as f64 * log2(charset))
+}
+
+// Changing this changes every derived password, so it must stay fixed.
+const DERIVE_CONTEXT: &str = "rcli 2025-10-01 pwd derive v1";
+
+/// Stateless, site specific password: the same master secret, site, user
+/// and counter always map to the same password under the same policy.
+pub fn process_pwd_derive(opts: PwdDeriveOpts) -> anyhow::Result<String> {
+    // the policy is shared with generation, but here the master secret is
+    // the only source of randomness
+    if opts.policy.seed.is_some() {
+        anyhow::bail!("--seed can't be used with pwd derive");
+    }
+    let master = match &opts.key {
+        Some(path) => Zeroizing::new(fs::read(path)?),
+        None => Zeroizing::new(rpassword::prompt_password("Master secret: ")?.into_bytes()),
+    };
+    if master.is_empty() {
+        anyhow::bail!("Master secret must not be empty");
+    }
+
+    let mut hasher = blake3::Hasher::new_derive_key(DERIVE_CONTEXT);
+    // length prefixes keep ("ab", "c") and ("a", "bc") apart
+    for field in [
+        master.as_slice(),
+        opts.site.to_lowercase().as_bytes(),
+        opts.user.as_bytes(),
+        &opts.counter.to_le_bytes(),
+    ] {
+        hasher.update(&(field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+    let mut rng = DerivedRng(hasher.finalize_xof());
+    let password = generate_pwd(&opts.policy, &mut rng)?;
+
+    if let Some(path) = &opts.policy.breach_db
+        && BreachDb::open(path)?.lookup(&password)? > 0
+    {
+        anyhow::bail!("Derived password is breached, use another --counter");
+    }
+    Ok(password)
+}
+
+// Feeds the BLAKE3 XOF stream into the regular generator, which reads it
+// four bytes at a time through `below`.
+struct DerivedRng(blake3::OutputReader);
+
+impl RngCore for DerivedRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0u8; 4];
+        self.0.fill(&mut buf);
+        u32::from_le_bytes(buf)
+    }
 
-    if opts.symbols {
-        chars.extend_from_slice(SYMBOL_CHARS);
-        password.push(
-            *SYMBOL_CHARS
-                .choose(&mut rng)
-                .expect("SYMBOL won't be empty"),
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.0.fill(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.fill(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for DerivedRng {}
+
+#[derive(Debug, Serialize)]
+pub struct PwdStrength {
+    pub score: u8,
+    pub guesses_log10: f64,
+    pub crack_times: Vec<CrackTime>,
+    pub patterns: Vec<PwdPattern>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breach_count: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrackTime {
+    pub scenario: &'static str,
+    pub seconds: f64,
+    pub display: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PwdPattern {
+    pub pattern: &'static str,
+    pub token: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub fn process_pwd_check(opts: PwdCheckOpts) -> anyhow::Result<Vec<PwdStrength>> {
+    let reader = BufReader::new(get_reader(&opts.input)?);
+    let words: Vec<&str> = opts.words.iter().map(String::as_str).collect();
+    let mut db = opts.breach_db.as_ref().map(BreachDb::open).transpose()?;
+
+    let mut reports = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let password = line.trim_end_matches('\r');
+        if password.is_empty() {
+            continue;
+        }
+        let mut report = process_pwd_strength(password, &words);
+        if let Some(db) = db.as_mut() {
+            report.breach_count = Some(db.lookup(password)?);
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+pub fn process_pwd_strength(password: &str, words: &[&str]) -> PwdStrength {
+    let entropy = zxcvbn(password, words);
+    let times = entropy.crack_times();
+    let crack_times = [
+        (
+            "online_throttling_100_per_hour",
+            times.online_throttling_100_per_hour(),
+        ),
+        (
+            "online_no_throttling_10_per_second",
+            times.online_no_throttling_10_per_second(),
+        ),
+        (
+            "offline_slow_hashing_1e4_per_second",
+            times.offline_slow_hashing_1e4_per_second(),
+        ),
+        (
+            "offline_fast_hashing_1e10_per_second",
+            times.offline_fast_hashing_1e10_per_second(),
+        ),
+    ]
+    .into_iter()
+    .map(|(scenario, time)| CrackTime {
+        scenario,
+        seconds: match time {
+            CrackTimeSeconds::Integer(i) => i as f64,
+            CrackTimeSeconds::Float(f) => f,
+        },
+        display: time.to_string(),
+    })
+    .collect();
+
+    let patterns = entropy
+        .sequence()
+        .iter()
+        .map(|m| PwdPattern {
+            pattern: pattern_name(&m.pattern),
+            token: m.token.clone(),
+            start: m.i,
+            end: m.j,
+        })
+        .collect();
+
+    let (warning, suggestions) = match entropy.feedback() {
+        Some(feedback) => (
+            feedback.warning().map(|w| w.to_string()),
+            feedback
+                .suggestions()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        ),
+        None => (None, Vec::new()),
+    };
+
+    PwdStrength {
+        score: entropy.score().into(),
+        guesses_log10: entropy.guesses_log10(),
+        crack_times,
+        patterns,
+        warning,
+        suggestions,
+        breach_count: None,
+    }
+}
+
+fn pattern_name(pattern: &MatchPattern) -> &'static str {
+    match pattern {
+        MatchPattern::Dictionary(_) => "dictionary",
+        MatchPattern::Spatial(_) => "spatial",
+        MatchPattern::Repeat(_) => "repeat",
+        MatchPattern::Sequence(_) => "sequence",
+        MatchPattern::Regex(_) => "regex",
+        MatchPattern::Date(_) => "date",
+        MatchPattern::BruteForce => "bruteforce",
+    }
+}
+
+impl Display for PwdStrength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "  score: {}/4", self.score)?;
+        writeln!(f, "  guesses: 10^{:.2}", self.guesses_log10)?;
+        writeln!(f, "  crack times:")?;
+        for time in &self.crack_times {
+            writeln!(f, "    {}: {}", time.scenario, time.display)?;
+        }
+        writeln!(f, "  patterns:")?;
+        for p in &self.patterns {
+            writeln!(
+                f,
+                "    {} [{}..={}] {:?}",
+                p.pattern, p.start, p.end, p.token
+            )?;
+        }
+        if let Some(warning) = &self.warning {
+            writeln!(f, "  warning: {}", warning)?;
+        }
+        for suggestion in &self.suggestions {
+            writeln!(f, "  suggestion: {}", suggestion)?;
+        }
+        match self.breach_count {
+            Some(0) => writeln!(f, "  breached: not found")?,
+            Some(count) => writeln!(f, "  breached: seen {} times", count)?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PwdCheckFormat;
+
+    #[test]
+    fn test_pwd_strength_weak() {
+        let report = process_pwd_strength("password1", &[]);
+        assert!(report.score <= 1);
+        assert_eq!(report.crack_times.len(), 4);
+        assert!(report.patterns.iter().any(|p| p.pattern == "dictionary"));
+        assert!(!report.suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_pwd_strength_user_words() {
+        let plain = process_pwd_strength("juventus1897", &[]);
+        let context = process_pwd_strength("juventus1897", &["juventus"]);
+        assert!(context.guesses_log10 <= plain.guesses_log10);
+    }
+
+    fn derive_opts(site: &str, counter: u32) -> PwdDeriveOpts {
+        PwdDeriveOpts {
+            site: site.to_string(),
+            user: "alice".to_string(),
+            counter,
+            key: Some("fixtures/blake3.txt".to_string()),
+            policy: PwdOpts {
+                length: 16,
+                uppercase: true,
+                lowercase: true,
+                numbers: true,
+                symbols: true,
+                pronounceable: false,
+                breach_db: None,
+                seed: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_pwd_derive_is_deterministic() -> anyhow::Result<()> {
+        let first = process_pwd_derive(derive_opts("example.com", 1))?;
+        assert_eq!(first, process_pwd_derive(derive_opts("Example.COM", 1))?);
+        assert_eq!(first, "Cbr-Ylz#zM(=lPQ5");
+        assert_ne!(first, process_pwd_derive(derive_opts("example.com", 2))?);
+        assert_ne!(first, process_pwd_derive(derive_opts("example.org", 1))?);
+
+        let mut seeded = derive_opts("example.com", 1);
+        seeded.policy.seed = Some(42);
+        assert!(process_pwd_derive(seeded).is_err());
+        Ok(())
+    }
+
+    // replays fixed u32 draws
+    struct ScriptedRng(std::vec::IntoIter<u32>);
+
+    impl RngCore for ScriptedRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0.next().expect("script is long enough")
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            rand_core::impls::next_u64_via_u32(self)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sampling_known_answers() {
+        // u32::MAX % 10 == 5, so the top 6 draws are rejected
+        let mut rng = ScriptedRng(vec![u32::MAX, u32::MAX - 5, u32::MAX - 6].into_iter());
+        assert_eq!(below(&mut rng, 10), 9);
+
+        // abcd -> abdc -> dbac -> dbac
+        let mut rng = ScriptedRng(vec![2, 0, 1].into_iter());
+        let mut bytes = *b"abcd";
+        shuffle(&mut rng, &mut bytes);
+        assert_eq!(&bytes, b"dbac");
+    }
+
+    fn pronounceable_opts(length: u8) -> PwdOpts {
+        PwdOpts {
+            length,
+            uppercase: true,
+            lowercase: true,
+            numbers: true,
+            symbols: true,
+            pronounceable: true,
+            breach_db: None,
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn test_pwd_pronounceable() -> anyhow::Result<()> {
+        for _ in 0..32 {
+            let password = process_pwd(pronounceable_opts(12))?;
+            let bytes = password.as_bytes();
+            assert_eq!(bytes.len(), 12);
+            for (i, c) in bytes[..9].iter().enumerate() {
+                let c = c.to_ascii_lowercase();
+                let set = if i % 2 == 0 {
+                    CONSONANT_CHARS
+                } else {
+                    VOWEL_CHARS
+                };
+                assert!(set.contains(&c), "{} in {}", c as char, password);
+            }
+            assert_eq!(
+                bytes[..9].iter().filter(|c| c.is_ascii_uppercase()).count(),
+                1
+            );
+            assert!(bytes[9..11].iter().all(u8::is_ascii_digit));
+            assert!(SYMBOL_CHARS.contains(&bytes[11]));
+        }
+        assert!(process_pwd(pronounceable_opts(4)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pwd_entropy_bits() -> anyhow::Result<()> {
+        let mut opts = pronounceable_opts(16);
+        let pronounceable = pwd_entropy_bits(&opts)?;
+        opts.pronounceable = false;
+        let random = pwd_entropy_bits(&opts)?;
+        let forced = 3.0 * 26f64.log2() + 10f64.log2();
+        assert!((random - (forced + 12.0 * 88f64.log2())).abs() < 1e-9);
+        assert!(random < 16.0 * 88f64.log2());
+        assert!(pronounceable < random);
+        Ok(())
+    }
+
+    fn seeded_opts(length: u8) -> PwdOpts {
+        PwdOpts {
+            length,
+            uppercase: true,
+            lowercase: true,
+            numbers: true,
+            symbols: true,
+            pronounceable: false,
+            breach_db: None,
+            seed: Some(42),
+        }
+    }
+
+    #[test]
+    fn test_pwd_seed_is_reproducible() -> anyhow::Result<()> {
+        assert_eq!(process_pwd(seeded_opts(16))?, "vcn4cy]9[9V4SV_3");
+        assert_eq!(process_pwd(seeded_opts(16))?, process_pwd(seeded_opts(16))?);
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        assert_eq!(
+            process_pwd_with_rng(seeded_opts(16), &mut rng)?,
+            process_pwd(seeded_opts(16))?
         );
+        Ok(())
     }
 
-    for _ in 0..(opts.length - password.len() as u8) {
-        let choose = chars
-            .choose(&mut rng)
-            .expect("chars won't be empty in this context");
-        password.push(*choose);
+    #[test]
+    fn test_pwd_charset_coverage() -> anyhow::Result<()> {
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..500 {
+            let password = process_pwd_with_rng(seeded_opts(8), &mut rng)?;
+            let bytes = password.as_bytes();
+            // every enabled class is always present
+            for set in [UPPERCASE_CHARS, LOWERCASE_CHARS, NUMBER_CHARS, SYMBOL_CHARS] {
+                assert!(bytes.iter().any(|c| set.contains(c)), "{}", password);
+            }
+            seen.extend(bytes.iter().copied());
+        }
+        assert_eq!(seen.len(), 26 + 26 + 10 + SYMBOL_CHARS.len());
+        Ok(())
     }
 
-    password.shuffle(&mut rng);
+    #[test]
+    fn test_pwd_uniformity() -> anyhow::Result<()> {
+        let mut rng = ChaCha20Rng::seed_from_u64(1234);
+        let mut opts = seeded_opts(32);
+        opts.uppercase = false;
+        opts.numbers = false;
+        opts.symbols = false;
 
-    let password = String::from_utf8(password)?;
+        let mut counts = [0u32; 26];
+        let rounds = 1000;
+        for _ in 0..rounds {
+            let password = process_pwd_with_rng(opts.clone(), &mut rng)?;
+            for c in password.bytes() {
+                counts[(c - b'a') as usize] += 1;
+            }
+        }
+        // chi-square with 25 degrees of freedom; 52.6 is the p = 0.001 cutoff
+        let expected = (rounds * 32) as f64 / 26.0;
+        let chi2: f64 = counts
+            .iter()
+            .map(|&n| (n as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi2 < 52.6, "chi2 = {}", chi2);
+        Ok(())
+    }
 
-    Ok(password)
+    #[test]
+    fn test_pwd_batch() -> anyhow::Result<()> {
+        let entries = process_pwd_batch(seeded_opts(20), 50)?;
+        assert_eq!(entries.len(), 50);
+        assert_eq!(entries[0].password, process_pwd(seeded_opts(20))?);
+        let unique: std::collections::HashSet<_> = entries.iter().map(|e| &e.password).collect();
+        assert_eq!(unique.len(), 50);
+        for entry in &entries {
+            assert_eq!(entry.length, 20);
+            assert_eq!(
+                entry.classes,
+                vec!["uppercase", "lowercase", "numbers", "symbols"]
+            );
+            assert_eq!(entry.entropy_bits, pwd_entropy_bits(&seeded_opts(20))?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pwd_check_breach_db() -> anyhow::Result<()> {
+        let reports = process_pwd_check(PwdCheckOpts {
+            input: "fixtures/pwd-candidates.txt".to_string(),
+            words: vec![],
+            breach_db: Some("fixtures/pwned-passwords-sha1.txt".to_string()),
+            format: PwdCheckFormat::Json,
+        })?;
+        let counts: Vec<_> = reports.iter().map(|r| r.breach_count).collect();
+        assert_eq!(counts, vec![Some(10434004), Some(41203), Some(0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pwd_batch_breach_db() -> anyhow::Result<()> {
+        let mut opts = seeded_opts(20);
+        opts.breach_db = Some("fixtures/pwned-passwords-sha1.txt".to_string());
+        let checked = process_pwd_batch(opts, 20)?;
+        let unchecked = process_pwd_batch(seeded_opts(20), 20)?;
+        let passwords = |entries: &[PwdEntry]| -> Vec<String> {
+            entries.iter().map(|e| e.password.clone()).collect()
+        };
+        assert_eq!(passwords(&checked), passwords(&unchecked));
+        Ok(())
+    }
 }
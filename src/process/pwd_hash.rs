@@ -0,0 +1,205 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::rngs::OsRng;
+use scrypt::Scrypt;
+
+use crate::{PwdBenchOpts, PwdHashAlgorithm, PwdHashOpts, PwdVerifyOpts, read_secret};
+
+// m_cost is in KiB, so the search starts at 8 MiB; anything lower isn't
+// worth recommending for a password hash.
+const MIN_M_COST: u32 = 8 * 1024;
+
+pub fn process_pwd_hash(opts: PwdHashOpts) -> anyhow::Result<String> {
+    let password = read_secret(&opts.input, "Password: ")?;
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = match opts.algorithm {
+        PwdHashAlgorithm::Argon2id => argon2id(opts.m_cost, opts.t_cost, opts.p_cost)?
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string(),
+        PwdHashAlgorithm::Scrypt => {
+            let params = scrypt::Params::new(opts.log_n, opts.scrypt_r, opts.scrypt_p, 32)?;
+            Scrypt
+                .hash_password_customized(password.as_bytes(), None, None, params, &salt)?
+                .to_string()
+        }
+        PwdHashAlgorithm::Bcrypt => bcrypt::non_truncating_hash(&password, opts.cost)?,
+    };
+    Ok(hash)
+}
+
+/// Checks the password against a PHC string (or a `$2b$` bcrypt hash), the
+/// algorithm and its parameters are taken from the hash itself.
+pub fn process_pwd_verify(opts: PwdVerifyOpts) -> anyhow::Result<bool> {
+    let password = read_secret(&opts.input, "Password: ")?;
+    if opts.hash.starts_with("$2") {
+        return Ok(bcrypt::non_truncating_verify(
+            password.as_str(),
+            &opts.hash,
+        )?);
+    }
+
+    let hash = PasswordHash::new(&opts.hash)?;
+    let verified = match hash.algorithm.as_str() {
+        "argon2id" | "argon2i" | "argon2d" => {
+            Argon2::default().verify_password(password.as_bytes(), &hash)
+        }
+        "scrypt" => Scrypt.verify_password(password.as_bytes(), &hash),
+        v => anyhow::bail!("Unsupported hash algorithm: {}", v),
+    };
+    match verified {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Debug)]
+pub struct PwdBenchResult {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub elapsed: Duration,
+}
+
+/// Finds Argon2id parameters that take at least `target_ms` on this machine:
+/// memory is doubled first (it is what hurts GPUs), then iterations are
+/// added once `max_m_cost` is reached. `max_m_cost` can't be below the
+/// 8 MiB the search starts from.
+pub fn process_pwd_bench(opts: PwdBenchOpts) -> anyhow::Result<PwdBenchResult> {
+    if opts.max_m_cost < MIN_M_COST {
+        anyhow::bail!("--max-m-cost must be at least {} KiB", MIN_M_COST);
+    }
+    let target = Duration::from_millis(opts.target_ms);
+    let (mut m_cost, mut t_cost, p_cost) = (MIN_M_COST, opts.t_cost, opts.p_cost);
+    let mut elapsed = time_argon2id(m_cost, t_cost, p_cost)?;
+    while elapsed < target {
+        if m_cost.saturating_mul(2) <= opts.max_m_cost {
+            m_cost *= 2;
+        } else {
+            t_cost += 1;
+        }
+        elapsed = time_argon2id(m_cost, t_cost, p_cost)?;
+    }
+
+    // the last doubling usually overshoots, scale memory back towards the target
+    if m_cost > MIN_M_COST && elapsed > target {
+        let scaled = (m_cost as f64 * target.as_secs_f64() / elapsed.as_secs_f64()) as u32;
+        let scaled = scaled.max(MIN_M_COST);
+        let scaled_elapsed = time_argon2id(scaled, t_cost, p_cost)?;
+        if scaled_elapsed >= target {
+            (m_cost, elapsed) = (scaled, scaled_elapsed);
+        }
+    }
+
+    Ok(PwdBenchResult {
+        m_cost,
+        t_cost,
+        p_cost,
+        elapsed,
+    })
+}
+
+fn argon2id(m_cost: u32, t_cost: u32, p_cost: u32) -> anyhow::Result<Argon2<'static>> {
+    let params = Params::new(m_cost, t_cost, p_cost, None)?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+fn time_argon2id(m_cost: u32, t_cost: u32, p_cost: u32) -> anyhow::Result<Duration> {
+    let argon2 = argon2id(m_cost, t_cost, p_cost)?;
+    let salt = SaltString::generate(&mut OsRng);
+    let start = Instant::now();
+    argon2.hash_password(b"rcli benchmark password", &salt)?;
+    Ok(start.elapsed())
+}
+
+impl Display for PwdBenchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "--m-cost {} --t-cost {} --p-cost {} ({} ms)",
+            self.m_cost,
+            self.t_cost,
+            self.p_cost,
+            self.elapsed.as_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_opts(algorithm: PwdHashAlgorithm) -> PwdHashOpts {
+        PwdHashOpts {
+            input: "fixtures/pwd-candidates.txt".to_string(),
+            algorithm,
+            m_cost: MIN_M_COST,
+            t_cost: 1,
+            p_cost: 1,
+            cost: 4,
+            log_n: 10,
+            scrypt_r: 8,
+            scrypt_p: 1,
+        }
+    }
+
+    fn verify(input: &str, hash: &str) -> anyhow::Result<bool> {
+        process_pwd_verify(PwdVerifyOpts {
+            input: input.to_string(),
+            hash: hash.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_pwd_hash_verify_round_trip() -> anyhow::Result<()> {
+        for (algorithm, prefix) in [
+            (PwdHashAlgorithm::Argon2id, "$argon2id$v=19$m=8192,t=1,p=1$"),
+            (PwdHashAlgorithm::Scrypt, "$scrypt$ln=10,r=8,p=1$"),
+            (PwdHashAlgorithm::Bcrypt, "$2b$04$"),
+        ] {
+            let hash = process_pwd_hash(hash_opts(algorithm))?;
+            assert!(hash.starts_with(prefix), "{}", hash);
+            // the first line of the candidates file is "password"
+            assert!(verify("fixtures/pwd-candidates.txt", &hash)?);
+            assert!(!verify("fixtures/blake3.txt", &hash)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pwd_verify_rejects_unknown_hash() {
+        assert!(verify("fixtures/pwd-candidates.txt", "not a hash").is_err());
+        assert!(
+            verify(
+                "fixtures/pwd-candidates.txt",
+                "$pbkdf2-sha256$i=1000$c2FsdA$aGFzaA"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_pwd_verify_bcrypt_does_not_truncate() -> anyhow::Result<()> {
+        // bcrypt only hashes 72 bytes, a longer password must not match its prefix
+        let password = "a".repeat(73);
+        let hash = bcrypt::hash(&password[..72], 4)?;
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("password.txt");
+        std::fs::write(&input, &password)?;
+        assert!(verify(&input.to_string_lossy(), &hash).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_pwd_bench_rejects_max_m_cost_below_the_minimum() {
+        let result = process_pwd_bench(PwdBenchOpts {
+            target_ms: 0,
+            max_m_cost: MIN_M_COST - 1,
+            t_cost: 1,
+            p_cost: 1,
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use uuid::Builder;
+
+use crate::{
+    RandBytesOpts, RandCheckTokenOpts, RandEncoding, RandNanoidOpts, RandTokenOpts, RandUlidOpts,
+    RandUuidOpts, base64_encode, read_secret,
+};
+
+const BASE62_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const CROCKFORD_CHARS: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+// 30 base62 characters carry ~178 bits, 6 more hold a CRC32
+const TOKEN_ENTROPY_LEN: usize = 30;
+const TOKEN_CHECKSUM_LEN: usize = 6;
+
+pub fn process_rand_bytes(opts: RandBytesOpts) -> anyhow::Result<String> {
+    let mut buf = vec![0u8; opts.length];
+    OsRng.fill_bytes(&mut buf);
+    let encoded = match opts.encoding {
+        RandEncoding::Hex => data_encoding::HEXLOWER.encode(&buf),
+        RandEncoding::Base64 => base64_encode(opts.format, &buf),
+    };
+    Ok(encoded)
+}
+
+pub fn process_rand_uuid(opts: RandUuidOpts) -> anyhow::Result<String> {
+    let uuid = match opts.version.as_str() {
+        "4" => {
+            let mut bytes = [0u8; 16];
+            OsRng.fill_bytes(&mut bytes);
+            Builder::from_random_bytes(bytes).into_uuid()
+        }
+        "7" => {
+            let mut bytes = [0u8; 10];
+            OsRng.fill_bytes(&mut bytes);
+            Builder::from_unix_timestamp_millis(unix_millis()?, &bytes).into_uuid()
+        }
+        v => anyhow::bail!("Unsupported UUID version: {}", v),
+    };
+    Ok(uuid.to_string())
+}
+
+pub fn process_rand_ulid(_opts: RandUlidOpts) -> anyhow::Result<String> {
+    let mut bytes = [0u8; 10];
+    OsRng.fill_bytes(&mut bytes);
+    Ok(ulid(unix_millis()?, bytes))
+}
+
+/// 48 bit millisecond timestamp and 80 random bits in Crockford base32.
+pub fn ulid(millis: u64, random: [u8; 10]) -> String {
+    let mut value = (millis as u128 & 0xffff_ffff_ffff) << 80;
+    for (i, b) in random.iter().enumerate() {
+        value |= (*b as u128) << (72 - i * 8);
+    }
+    // 26 characters * 5 bits = 130 bits, the first one only holds 3
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD_CHARS[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+pub fn process_rand_nanoid(opts: RandNanoidOpts) -> anyhow::Result<String> {
+    let alphabet: Vec<char> = opts.alphabet.chars().collect();
+    if alphabet.len() < 2 {
+        anyhow::bail!("Alphabet needs at least 2 characters");
+    }
+    let mut rng = OsRng;
+    Ok((0..opts.length)
+        .map(|_| *alphabet.choose(&mut rng).expect("alphabet won't be empty"))
+        .collect())
+}
+
+/// GitHub style token: `<prefix>_<30 random base62><6 base62 CRC32>`, so a
+/// typo or truncation can be caught offline without a database lookup.
+pub fn process_rand_token(opts: RandTokenOpts) -> anyhow::Result<String> {
+    if opts.prefix.is_empty() || !opts.prefix.bytes().all(|c| c.is_ascii_alphanumeric()) {
+        anyhow::bail!("Token prefix must be non-empty and alphanumeric");
+    }
+    let mut rng = OsRng;
+    let entropy: String = (0..TOKEN_ENTROPY_LEN)
+        .map(|_| {
+            *BASE62_CHARS
+                .choose(&mut rng)
+                .expect("BASE62 won't be empty") as char
+        })
+        .collect();
+    let body = format!("{}_{}", opts.prefix, entropy);
+    let checksum = base62(crc32fast::hash(body.as_bytes()), TOKEN_CHECKSUM_LEN);
+    Ok(format!("{}{}", body, checksum))
+}
+
+pub fn process_rand_check_token(opts: RandCheckTokenOpts) -> anyhow::Result<bool> {
+    let token = read_secret(&opts.input, "Token: ")?;
+    Ok(check_token(token.trim()))
+}
+
+pub fn check_token(token: &str) -> bool {
+    let Some((prefix, rest)) = token.split_once('_') else {
+        return false;
+    };
+    if prefix.is_empty() || rest.len() != TOKEN_ENTROPY_LEN + TOKEN_CHECKSUM_LEN || !rest.is_ascii()
+    {
+        return false;
+    }
+    let (body, checksum) = token.split_at(token.len() - TOKEN_CHECKSUM_LEN);
+    base62(crc32fast::hash(body.as_bytes()), TOKEN_CHECKSUM_LEN) == checksum
+}
+
+// Fixed width base62, zero padded on the left.
+fn base62(mut n: u32, width: usize) -> String {
+    let mut out = vec![BASE62_CHARS[0]; width];
+    for slot in out.iter_mut().rev() {
+        *slot = BASE62_CHARS[(n % 62) as usize];
+        n /= 62;
+    }
+    String::from_utf8(out).expect("base62 is ascii")
+}
+
+fn unix_millis() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ulid_encoding() {
+        assert_eq!(ulid(0, [0; 10]), "00000000000000000000000000");
+        assert_eq!(ulid(u64::MAX, [0xff; 10]), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+        // timestamp prefix from the ULID spec examples
+        assert!(ulid(1469918176385, [0; 10]).starts_with("01ARYZ6S41"));
+    }
+
+    #[test]
+    fn test_uuid_versions() -> anyhow::Result<()> {
+        for version in ["4", "7"] {
+            let uuid = process_rand_uuid(RandUuidOpts {
+                version: version.to_string(),
+            })?;
+            let uuid = uuid::Uuid::parse_str(&uuid)?;
+            assert_eq!(uuid.get_version_num().to_string(), version);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_checksum() -> anyhow::Result<()> {
+        let token = process_rand_token(RandTokenOpts {
+            prefix: "rcli".to_string(),
+        })?;
+        assert!(token.starts_with("rcli_"));
+        assert_eq!(token.len(), 5 + TOKEN_ENTROPY_LEN + TOKEN_CHECKSUM_LEN);
+        assert!(check_token(&token));
+
+        let mut typo = token.into_bytes();
+        typo[10] = if typo[10] == b'a' { b'b' } else { b'a' };
+        assert!(!check_token(&String::from_utf8(typo)?));
+        assert!(!check_token("rcli_short"));
+        assert!(!check_token("no-separator"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nanoid_alphabet() -> anyhow::Result<()> {
+        let id = process_rand_nanoid(RandNanoidOpts {
+            length: 64,
+            alphabet: "ab".to_string(),
+        })?;
+        assert_eq!(id.len(), 64);
+        assert!(id.chars().all(|c| c == 'a' || c == 'b'));
+        Ok(())
+    }
+}
@@ -1,15 +1,65 @@
 use crate::{
-    PwdOpts, TextKeyGenerateOpts, TextSignFormat, TextSignOpts, TextVerifyOpts, get_reader,
-    process_pwd,
+    Ed25519Key, KeyKind, TextKeyGenerateOpts, TextKeyType, TextSignFormat, TextSignOpts,
+    TextVerifyOpts, detect_key_kind, get_reader, key_id, load_ed25519_key, read_key,
 };
 use base64::Engine;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::prelude::BASE64_URL_SAFE_NO_PAD;
-use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use ed25519_dalek::{Digest, Sha512, Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use std::fmt::Display;
 use std::fs;
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::Path;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Why a key or signature was rejected, so malformed input surfaces as an
+/// error instead of a panic and callers can tell the cases apart.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextError {
+    WrongLength {
+        what: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    BadEncoding {
+        what: &'static str,
+        reason: String,
+    },
+    WrongAlgorithm {
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::WrongLength {
+                what,
+                expected,
+                actual,
+            } => write!(f, "{} must be {} bytes, got {}", what, expected, actual),
+            TextError::BadEncoding { what, reason } => write!(f, "Invalid {}: {}", what, reason),
+            TextError::WrongAlgorithm { expected, actual } => {
+                write!(f, "Expected {}, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+fn fixed<const N: usize>(what: &'static str, bytes: &[u8]) -> Result<[u8; N], TextError> {
+    bytes.try_into().map_err(|_| TextError::WrongLength {
+        what,
+        expected: N,
+        actual: bytes.len(),
+    })
+}
 
 pub trait TextSign {
     fn sign(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<u8>>;
@@ -26,9 +76,15 @@ pub trait KeyLoader {
 }
 
 pub trait KeyGenerator {
-    fn generate() -> anyhow::Result<Vec<Vec<u8>>>;
+    fn generate() -> anyhow::Result<Vec<Vec<u8>>> {
+        Self::generate_with_rng(&mut OsRng)
+    }
+
+    fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> anyhow::Result<Vec<Vec<u8>>>;
 }
 
+// SigningKey wipes itself on drop, the BLAKE3 key has to be told to.
+#[derive(Zeroize, ZeroizeOnDrop)]
 pub struct Blake3 {
     key: [u8; 32],
 }
@@ -38,31 +94,28 @@ impl Blake3 {
         Self { key }
     }
 
-    pub fn try_new(key: &[u8]) -> anyhow::Result<Self> {
-        let key = &key[..32];
-        let key = key.try_into()?;
-        let signer = Blake3::new(key);
-        Ok(signer)
+    pub fn try_new(key: &[u8]) -> Result<Self, TextError> {
+        Ok(Blake3::new(fixed("BLAKE3 key", key)?))
+    }
+}
+
+impl Blake3 {
+    pub fn key_id(&self) -> anyhow::Result<String> {
+        key_id(KeyKind::Blake3, &self.key)
     }
 }
 
 impl KeyLoader for Blake3 {
     fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let key = fs::read(path)?;
-        Self::try_new(&key)
+        let key = read_key(path, KeyKind::Blake3)?;
+        Ok(Self::try_new(&key)?)
     }
 }
 
 impl KeyGenerator for Blake3 {
-    fn generate() -> anyhow::Result<Vec<Vec<u8>>> {
-        let key = process_pwd(PwdOpts {
-            length: 32,
-            uppercase: true,
-            lowercase: true,
-            numbers: true,
-            symbols: true,
-        })?;
-        let key = key.as_bytes().to_vec();
+    fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut key = vec![0u8; 32];
+        rng.fill_bytes(&mut key);
         Ok(vec![key])
     }
 }
@@ -76,24 +129,24 @@ impl Ed255195Signer {
         Self { key }
     }
 
-    pub fn try_new(key: &[u8]) -> anyhow::Result<Self> {
-        let key = SigningKey::from_bytes(key.try_into()?);
-        let signer = Ed255195Signer::new(key);
-        Ok(signer)
+    pub fn try_new(key: &[u8]) -> Result<Self, TextError> {
+        let key = SigningKey::from_bytes(&fixed("Ed25519 secret key", key)?);
+        Ok(Ed255195Signer::new(key))
     }
 }
 
 impl KeyLoader for Ed255195Signer {
     fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let key = fs::read(path)?;
-        Self::try_new(&key)
+        match load_ed25519_key(path, KeyKind::Ed25519Secret)? {
+            Ed25519Key::Secret(key) => Ok(Self::new(key)),
+            Ed25519Key::Public(_) => anyhow::bail!("Signing needs a private key"),
+        }
     }
 }
 
 impl KeyGenerator for Ed255195Signer {
-    fn generate() -> anyhow::Result<Vec<Vec<u8>>> {
-        let mut csprng = OsRng;
-        let sk: SigningKey = SigningKey::generate(&mut csprng);
+    fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> anyhow::Result<Vec<Vec<u8>>> {
+        let sk: SigningKey = SigningKey::generate(rng);
         let pk: VerifyingKey = (&sk).into();
         let sk = sk.to_bytes().to_vec();
         Ok(vec![sk, pk.to_bytes().to_vec()])
@@ -109,43 +162,55 @@ impl Ed255195Verifier {
         Self { key }
     }
 
-    pub fn try_new(key: &[u8]) -> anyhow::Result<Self> {
-        let key = (&key[..32]).try_into()?;
-        let key = VerifyingKey::from_bytes(key)?;
-        let verifier = Ed255195Verifier::new(key);
-        Ok(verifier)
+    pub fn try_new(key: &[u8]) -> Result<Self, TextError> {
+        let key = VerifyingKey::from_bytes(&fixed("Ed25519 public key", key)?).map_err(|e| {
+            TextError::BadEncoding {
+                what: "Ed25519 public key",
+                reason: e.to_string(),
+            }
+        })?;
+        Ok(Ed255195Verifier::new(key))
     }
+}
 
-    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let key = fs::read(path)?;
-        Self::try_new(&key)
+impl Ed255195Verifier {
+    pub fn key_id(&self) -> anyhow::Result<String> {
+        key_id(KeyKind::Ed25519Public, self.key.as_bytes())
     }
 }
 
 impl KeyLoader for Ed255195Verifier {
     fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let key = fs::read(path)?;
-        Self::try_new(&key)
+        let key = load_ed25519_key(path, KeyKind::Ed25519Public)?;
+        Ok(Self::new(key.verifying_key()))
+    }
+}
+
+impl Blake3 {
+    // the incremental hasher gives the same MAC as `keyed_hash` on chunks
+    fn mac(&self, reader: &mut dyn Read) -> anyhow::Result<blake3::Hash> {
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        io::copy(reader, &mut hasher)?;
+        Ok(hasher.finalize())
     }
 }
 
 impl TextSign for Blake3 {
     fn sign(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-
-        Ok(blake3::keyed_hash(&self.key, &buf).as_bytes().to_vec())
+        Ok(self.mac(reader)?.as_bytes().to_vec())
     }
 }
 
 impl TextVerify for Blake3 {
+    // comparing blake3::Hash values is constant time, unlike byte slices
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> anyhow::Result<bool> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        Ok(blake3::keyed_hash(&self.key, &buf).as_bytes() == sig)
+        let expected = blake3::Hash::from_bytes(fixed("BLAKE3 MAC", sig)?);
+        Ok(self.mac(reader)? == expected)
     }
 }
 
+// Pure Ed25519 hashes the message twice, so it has to hold all of it in
+// memory. Ed25519ph (RFC 8032) signs a streamed SHA-512 digest instead.
 impl TextSign for Ed255195Signer {
     fn sign(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<u8>> {
         let mut buf = Vec::new();
@@ -155,48 +220,571 @@ impl TextSign for Ed255195Signer {
     }
 }
 
+impl Ed255195Signer {
+    pub fn sign_prehashed(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<u8>> {
+        let signature = self.key.sign_prehashed(prehash(reader)?, None)?;
+        Ok(signature.to_bytes().to_vec())
+    }
+}
+
 impl TextVerify for Ed255195Verifier {
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> anyhow::Result<bool> {
+        let signature = Signature::from_bytes(&fixed("Ed25519 signature", sig)?);
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
-        let sig = (&sig[..64]).try_into()?;
-        let signature = Signature::from_bytes(sig);
         Ok(self.key.verify(&buf, &signature).is_ok())
     }
 }
 
+impl Ed255195Verifier {
+    pub fn verify_prehashed(&self, reader: &mut dyn Read, sig: &[u8]) -> anyhow::Result<bool> {
+        let signature = Signature::from_bytes(&fixed("Ed25519 signature", sig)?);
+        Ok(self
+            .key
+            .verify_prehashed(prehash(reader)?, None, &signature)
+            .is_ok())
+    }
+}
+
+fn prehash(reader: &mut dyn Read) -> anyhow::Result<Sha512> {
+    let mut digest = Sha512::new();
+    io::copy(reader, &mut digest)?;
+    Ok(digest)
+}
+
+/// Splits a `<format>:<base64>` signature. Untagged signatures from before
+/// the tags were added return `None`.
+pub fn parse_signature(signature: &str) -> anyhow::Result<(Option<TextSignFormat>, Vec<u8>)> {
+    let (format, encoded) = match signature.trim().split_once(':') {
+        Some((tag, encoded)) => {
+            let format = tag
+                .parse::<TextSignFormat>()
+                .map_err(|_| TextError::WrongAlgorithm {
+                    expected: "blake3, ed25519 or ed25519ph".to_string(),
+                    actual: tag.to_string(),
+                })?;
+            (Some(format), encoded)
+        }
+        None => (None, signature.trim()),
+    };
+    let signature = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|e| TextError::BadEncoding {
+            what: "signature",
+            reason: e.to_string(),
+        })?;
+    Ok((format, signature))
+}
+
+/// Signs `opts.input` and returns the signature tagged with its format, as
+/// in `ed25519ph:<base64>`.
 pub fn process_text_sign(opts: TextSignOpts) -> anyhow::Result<String> {
     let mut reader = get_reader(&opts.input)?;
-    let signed = match opts.format {
+    sign_reader(opts.format, &opts.key, &mut reader)
+}
+
+/// Outcome of `text verify`. Each one has its own exit code so scripts can
+/// tell a forged signature from a broken invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyResult {
+    Valid,
+    Mismatch,
+    Usage,
+    Malformed,
+    BadKey,
+    BadInput,
+}
+
+impl VerifyResult {
+    /// 0 valid, 1 mismatch, 2 usage error like clap's own, 3 malformed
+    /// signature, 4 unusable key, 5 input that can't be read.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            VerifyResult::Valid => 0,
+            VerifyResult::Mismatch => 1,
+            VerifyResult::Usage => 2,
+            VerifyResult::Malformed => 3,
+            VerifyResult::BadKey => 4,
+            VerifyResult::BadInput => 5,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    pub result: VerifyResult,
+    pub algorithm: Option<String>,
+    pub key_id: Option<String>,
+    /// BLAKE3 of the input, to tie the report to the exact bytes checked
+    pub input_digest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The key decides between BLAKE3 and Ed25519, the signature's tag only picks
+/// the Ed25519 variant and has to agree with `opts.format`. Failures end up
+/// in the report rather than as an error.
+pub fn process_text_verify(opts: TextVerifyOpts) -> VerifyReport {
+    let mut report = VerifyReport {
+        result: VerifyResult::Malformed,
+        algorithm: None,
+        key_id: None,
+        input_digest: None,
+        error: None,
+    };
+    report.result = match check_signature(&opts, &mut report) {
+        Ok(true) => VerifyResult::Valid,
+        Ok(false) => VerifyResult::Mismatch,
+        Err((result, e)) => {
+            report.error = Some(format!("{:#}", e));
+            result
+        }
+    };
+    report
+}
+
+fn check_signature(
+    opts: &TextVerifyOpts,
+    report: &mut VerifyReport,
+) -> Result<bool, (VerifyResult, anyhow::Error)> {
+    let usage = |e| (VerifyResult::Usage, e);
+    let malformed = |e| (VerifyResult::Malformed, e);
+    let bad_key = |e| (VerifyResult::BadKey, e);
+    let bad_input = |e| (VerifyResult::BadInput, e);
+    let (tag, sig) = parse_signature(&opts.signature).map_err(malformed)?;
+    let kind = key_kind(&opts.key).map_err(bad_key)?;
+    let requested = requested_format(tag, opts.format).map_err(usage)?;
+    let format = key_format(kind, requested, opts.format.is_some()).map_err(|e| match kind {
+        // a raw key needs --format
+        None => usage(e),
+        Some(KeyKind::Symmetric) => bad_key(e),
+        // a signature made with a different kind of key
+        Some(_) => malformed(e),
+    })?;
+    report.algorithm = Some(format.to_string());
+
+    let mut reader = DigestReader {
+        inner: get_reader(&opts.input).map_err(bad_input)?,
+        hasher: blake3::Hasher::new(),
+    };
+    let verified = match format {
+        TextSignFormat::Blake3 => {
+            let key = Blake3::load(&opts.key).map_err(bad_key)?;
+            report.key_id = Some(key.key_id().map_err(bad_key)?);
+            key.verify(&mut reader, &sig)
+        }
+        TextSignFormat::Ed25519 | TextSignFormat::Ed25519ph => {
+            let key = Ed255195Verifier::load(&opts.key).map_err(bad_key)?;
+            report.key_id = Some(key.key_id().map_err(bad_key)?);
+            if format == TextSignFormat::Ed25519 {
+                key.verify(&mut reader, &sig)
+            } else {
+                key.verify_prehashed(&mut reader, &sig)
+            }
+        }
+    }
+    .map_err(|e| {
+        // the signature's length is checked before any input is read
+        if e.is::<io::Error>() {
+            bad_input(e)
+        } else {
+            malformed(e)
+        }
+    })?;
+    report.input_digest = Some(format!("blake3:{}", reader.hasher.finalize().to_hex()));
+    Ok(verified)
+}
+
+// Hashes everything the verifier reads, for the report's input digest.
+struct DigestReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+pub fn sign_reader(
+    format: TextSignFormat,
+    key: impl AsRef<Path>,
+    reader: &mut dyn Read,
+) -> anyhow::Result<String> {
+    signature_format(key_kind(&key)?, None, Some(format))?;
+    let signed = match format {
         TextSignFormat::Blake3 => {
-            let signer = Blake3::load(&opts.key)?;
-            signer.sign(&mut reader)?
+            let signer = Blake3::load(key)?;
+            signer.sign(reader)?
         }
         TextSignFormat::Ed25519 => {
-            let signer = Ed255195Signer::load(opts.key)?;
-            signer.sign(&mut reader)?
+            let signer = Ed255195Signer::load(key)?;
+            signer.sign(reader)?
         }
+        TextSignFormat::Ed25519ph => Ed255195Signer::load(key)?.sign_prehashed(reader)?,
     };
     let signed = BASE64_URL_SAFE_NO_PAD.encode(&signed);
-    Ok(signed)
+    Ok(format!("{}:{}", format, signed))
 }
 
-pub fn process_text_verify(opts: TextVerifyOpts) -> anyhow::Result<bool> {
-    let mut reader = get_reader(&opts.input)?;
-    let sig = URL_SAFE_NO_PAD.decode(opts.signature)?;
-    let verified = match opts.format {
+pub fn verify_reader(
+    format: Option<TextSignFormat>,
+    key: impl AsRef<Path>,
+    reader: &mut dyn Read,
+    signature: &str,
+) -> anyhow::Result<bool> {
+    let (tag, sig) = parse_signature(signature)?;
+    let verified = match signature_format(key_kind(&key)?, tag, format)? {
         TextSignFormat::Blake3 => {
-            let signer = Blake3::load(&opts.key)?;
-            signer.verify(&mut reader, &sig)?
+            let signer = Blake3::load(key)?;
+            signer.verify(reader, &sig)?
         }
-        TextSignFormat::Ed25519 => Ed255195Verifier::load(opts.key)?.verify(&mut reader, &sig)?,
+        TextSignFormat::Ed25519 => Ed255195Verifier::load(key)?.verify(reader, &sig)?,
+        TextSignFormat::Ed25519ph => Ed255195Verifier::load(key)?.verify_prehashed(reader, &sig)?,
     };
     Ok(verified)
 }
 
+fn key_kind(key: impl AsRef<Path>) -> anyhow::Result<Option<KeyKind>> {
+    detect_key_kind(&fs::read(key)?)
+}
+
+fn signature_format(
+    key: Option<KeyKind>,
+    tag: Option<TextSignFormat>,
+    format: Option<TextSignFormat>,
+) -> anyhow::Result<TextSignFormat> {
+    key_format(key, requested_format(tag, format)?, format.is_some())
+}
+
+// An explicit --format has to agree with the signature's tag.
+fn requested_format(
+    tag: Option<TextSignFormat>,
+    format: Option<TextSignFormat>,
+) -> anyhow::Result<Option<TextSignFormat>> {
+    match (tag, format) {
+        (Some(tag), Some(format)) if tag != format => Err(TextError::WrongAlgorithm {
+            expected: format!("{} signature", format),
+            actual: format!("{} signature", tag),
+        }
+        .into()),
+        (tag, format) => Ok(format.or(tag)),
+    }
+}
+
+// The key picks between a BLAKE3 MAC and Ed25519. Were it the tag, anyone
+// could tag a MAC keyed with a public key `blake3:` and have it verify. The
+// tag only picks the Ed25519 variant, and a raw 32 byte key, which could be
+// either, is only a MAC key when `--format` says so.
+fn key_format(
+    key: Option<KeyKind>,
+    requested: Option<TextSignFormat>,
+    explicit: bool,
+) -> anyhow::Result<TextSignFormat> {
+    use TextSignFormat::{Blake3, Ed25519, Ed25519ph};
+    let ed25519 = matches!(key, Some(KeyKind::Ed25519Secret | KeyKind::Ed25519Public));
+    match (key, requested) {
+        (Some(KeyKind::Blake3), None | Some(Blake3)) => Ok(Blake3),
+        (_, None) if ed25519 => Ok(Ed25519),
+        (_, Some(format @ (Ed25519 | Ed25519ph))) if ed25519 || key.is_none() => Ok(format),
+        (None, Some(Blake3)) if explicit => Ok(Blake3),
+        (None, _) => anyhow::bail!("A raw 32 byte key could be BLAKE3 or Ed25519, pass --format"),
+        (Some(KeyKind::Symmetric), _) => Err(TextError::WrongAlgorithm {
+            expected: "a BLAKE3 or Ed25519 key".to_string(),
+            actual: "a symmetric key".to_string(),
+        }
+        .into()),
+        (Some(KeyKind::Blake3), Some(requested)) => Err(TextError::WrongAlgorithm {
+            expected: "blake3 signature for a BLAKE3 key".to_string(),
+            actual: format!("{} signature", requested),
+        }
+        .into()),
+        // an Ed25519 key asked for a BLAKE3 MAC
+        (Some(_), _) => Err(TextError::WrongAlgorithm {
+            expected: "ed25519 or ed25519ph signature for an Ed25519 key".to_string(),
+            actual: "blake3 signature".to_string(),
+        }
+        .into()),
+    }
+}
+
 pub fn process_text_generate(opts: TextKeyGenerateOpts) -> anyhow::Result<Vec<Vec<u8>>> {
+    match opts.seed {
+        Some(seed) => process_text_generate_with_rng(opts, &mut ChaCha20Rng::seed_from_u64(seed)),
+        None => process_text_generate_with_rng(opts, &mut OsRng),
+    }
+}
+
+pub fn process_text_generate_with_rng<R: RngCore + CryptoRng>(
+    opts: TextKeyGenerateOpts,
+    rng: &mut R,
+) -> anyhow::Result<Vec<Vec<u8>>> {
     match opts.format {
-        TextSignFormat::Blake3 => Blake3::generate(),
-        TextSignFormat::Ed25519 => Ed255195Signer::generate(),
+        // a BLAKE3 MAC key, a symmetric cipher key and an X25519 secret are
+        // all 32 random bytes
+        TextKeyType::Blake3 | TextKeyType::Symmetric | TextKeyType::X25519 => {
+            Blake3::generate_with_rng(rng)
+        }
+        TextKeyType::Ed25519 => Ed255195Signer::generate_with_rng(rng),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_key_generate_is_reproducible() -> anyhow::Result<()> {
+        for format in [TextKeyType::Blake3, TextKeyType::Ed25519] {
+            let generate = || {
+                process_text_generate(TextKeyGenerateOpts {
+                    format,
+                    output: PathBuf::from("fixtures"),
+                    seed: Some(42),
+                })
+            };
+            assert_eq!(generate()?, generate()?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed25519_generate_round_trip() -> anyhow::Result<()> {
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let keys = Ed255195Signer::generate_with_rng(&mut rng)?;
+        let signer = Ed255195Signer::try_new(&keys[0])?;
+        let verifier = Ed255195Verifier::try_new(&keys[1])?;
+        let sig = signer.sign(&mut &b"hello"[..])?;
+        assert!(verifier.verify(&mut &b"hello"[..], &sig)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_and_armored_keys_sign_alike() -> anyhow::Result<()> {
+        let legacy = Blake3::load("fixtures/blake3.txt")?;
+        let key = read_key("fixtures/blake3.txt", KeyKind::Blake3)?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("blake3_armored.txt");
+        std::fs::write(
+            &path,
+            crate::KeyFile::new(KeyKind::Blake3, key.to_vec())?.to_armored(),
+        )?;
+        let armored = Blake3::load(&path)?;
+        assert_eq!(
+            legacy.sign(&mut &b"hello"[..])?,
+            armored.sign(&mut &b"hello"[..])?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_signatures() -> anyhow::Result<()> {
+        // bigger than the chunks io::copy reads
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        let blake3 = Blake3::load("fixtures/blake3.txt")?;
+        let key = read_key("fixtures/blake3.txt", KeyKind::Blake3)?;
+        assert_eq!(
+            blake3.sign(&mut &data[..])?,
+            blake3::keyed_hash(key[..].try_into()?, &data).as_bytes()
+        );
+
+        let signer = Ed255195Signer::load("fixtures/ed25519.sk")?;
+        let verifier = Ed255195Verifier::load("fixtures/ed25519.pk")?;
+        let sig = signer.sign_prehashed(&mut &data[..])?;
+        assert!(verifier.verify_prehashed(&mut &data[..], &sig)?);
+        assert!(!verifier.verify_prehashed(&mut &data[1..], &sig)?);
+        // prehashed and pure signatures aren't interchangeable
+        assert!(!verifier.verify(&mut &data[..], &sig)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_signature_tags() -> anyhow::Result<()> {
+        let sign = |format| {
+            process_text_sign(TextSignOpts {
+                input: "fixtures/otp.txt".to_string(),
+                key: "fixtures/ed25519.sk".to_string(),
+                format,
+            })
+        };
+        let verify = |signature: &str, format| {
+            process_text_verify(TextVerifyOpts {
+                input: "fixtures/otp.txt".to_string(),
+                key: "fixtures/ed25519.pk".to_string(),
+                signature: signature.to_string(),
+                format,
+                json: false,
+            })
+            .result
+        };
+        let pure = sign(TextSignFormat::Ed25519)?;
+        let prehashed = sign(TextSignFormat::Ed25519ph)?;
+        assert!(pure.starts_with("ed25519:"));
+        assert!(prehashed.starts_with("ed25519ph:"));
+        assert_eq!(verify(&prehashed, None), VerifyResult::Valid);
+        assert_eq!(
+            verify(&prehashed, Some(TextSignFormat::Ed25519)),
+            VerifyResult::Usage
+        );
+        // untagged signatures still verify with an explicit format
+        let (_, untagged) = pure.split_once(':').expect("tagged");
+        assert_eq!(
+            verify(untagged, Some(TextSignFormat::Ed25519)),
+            VerifyResult::Valid
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_report() -> anyhow::Result<()> {
+        let signature = process_text_sign(TextSignOpts {
+            input: "fixtures/otp.txt".to_string(),
+            key: "fixtures/blake3.txt".to_string(),
+            format: TextSignFormat::Blake3,
+        })?;
+        let verify = |input: &str, key: &str, signature: &str| {
+            process_text_verify(TextVerifyOpts {
+                input: input.to_string(),
+                key: key.to_string(),
+                signature: signature.to_string(),
+                format: None,
+                json: true,
+            })
+        };
+
+        let report = verify("fixtures/otp.txt", "fixtures/blake3.txt", &signature);
+        assert_eq!(report.result, VerifyResult::Valid);
+        assert_eq!(report.algorithm.as_deref(), Some("blake3"));
+        let key = read_key("fixtures/blake3.txt", KeyKind::Blake3)?;
+        assert_eq!(report.key_id, Some(key_id(KeyKind::Blake3, &key)?));
+        let otp = std::fs::read("fixtures/otp.txt")?;
+        assert_eq!(
+            report.input_digest,
+            Some(format!("blake3:{}", blake3::hash(&otp).to_hex()))
+        );
+        assert!(report.error.is_none());
+
+        let mismatch = verify("fixtures/index.html", "fixtures/blake3.txt", &signature);
+        assert_eq!(mismatch.result, VerifyResult::Mismatch);
+        assert_eq!(mismatch.result.exit_code(), 1);
+        let malformed = verify("fixtures/otp.txt", "fixtures/blake3.txt", "blake3:!!");
+        assert_eq!(malformed.result, VerifyResult::Malformed);
+        assert!(malformed.error.is_some());
+        let bad_key = verify("fixtures/otp.txt", "fixtures/missing.key", &signature);
+        assert_eq!(bad_key.result, VerifyResult::BadKey);
+        assert_eq!(bad_key.result.exit_code(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blake3_tag_cannot_forge_ed25519() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let pk = Ed25519Key::Public(Ed255195Verifier::load("fixtures/ed25519.pk")?.key);
+        let pem = dir.path().join("ed25519.pem");
+        fs::write(&pem, crate::encode_ed25519_key(&pk, crate::KeyFormat::Pem)?)?;
+        let armored = dir.path().join("ed25519.pub");
+        fs::write(
+            &armored,
+            crate::KeyFile::new(KeyKind::Ed25519Public, pk.to_bytes().to_vec())?.to_armored(),
+        )?;
+
+        let input = fs::read("fixtures/otp.txt")?;
+        for key in [PathBuf::from("fixtures/ed25519.pk"), pem, armored] {
+            // what the verifier would have keyed the MAC with, all public
+            let public = fs::read(&key)?;
+            let mac = blake3::keyed_hash(public[..32].try_into()?, &input);
+            let forged = format!("blake3:{}", URL_SAFE_NO_PAD.encode(mac.as_bytes()));
+            let report = process_text_verify(TextVerifyOpts {
+                input: "fixtures/otp.txt".to_string(),
+                key: key.to_string_lossy().into_owned(),
+                signature: forged.clone(),
+                format: None,
+                json: false,
+            });
+            assert_ne!(report.result, VerifyResult::Valid, "{}", key.display());
+            assert!(verify_reader(None, &key, &mut input.as_slice(), &forged).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        assert_eq!(
+            Blake3::try_new(&[0; 31]).err(),
+            Some(TextError::WrongLength {
+                what: "BLAKE3 key",
+                expected: 32,
+                actual: 31,
+            })
+        );
+        // trailing bytes used to be silently ignored
+        assert!(Blake3::try_new(&[0; 33]).is_err());
+        assert!(matches!(
+            Ed255195Verifier::try_new(&[]),
+            Err(TextError::WrongLength { actual: 0, .. })
+        ));
+        assert!(matches!(
+            parse_signature("sha256:AAAA"),
+            Err(e) if matches!(e.downcast_ref(), Some(TextError::WrongAlgorithm { .. }))
+        ));
+        assert!(matches!(
+            parse_signature("blake3:!!"),
+            Err(e) if matches!(e.downcast_ref(), Some(TextError::BadEncoding { .. }))
+        ));
+        let err = signature_format(
+            None,
+            Some(TextSignFormat::Blake3),
+            Some(TextSignFormat::Ed25519),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected ed25519 signature, got blake3 signature"
+        );
+    }
+
+    #[test]
+    fn test_short_signatures_are_errors() -> anyhow::Result<()> {
+        let blake3 = Blake3::load("fixtures/blake3.txt")?;
+        let verifier = Ed255195Verifier::load("fixtures/ed25519.pk")?;
+        for len in [0, 1, 31, 63] {
+            let sig = vec![0; len];
+            assert!(blake3.verify(&mut &b"hello"[..], &sig).is_err());
+            assert!(verifier.verify(&mut &b"hello"[..], &sig).is_err());
+            assert!(verifier.verify_prehashed(&mut &b"hello"[..], &sig).is_err());
+        }
+        Ok(())
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_key_parsing_never_panics(key in proptest::collection::vec(proptest::num::u8::ANY, 0..96)) {
+            proptest::prop_assert_eq!(Blake3::try_new(&key).is_ok(), key.len() == 32);
+            proptest::prop_assert_eq!(Ed255195Signer::try_new(&key).is_ok(), key.len() == 32);
+            let _ = Ed255195Verifier::try_new(&key);
+        }
+
+        #[test]
+        fn prop_verify_never_panics(
+            data in proptest::collection::vec(proptest::num::u8::ANY, 0..256),
+            sig in proptest::collection::vec(proptest::num::u8::ANY, 0..128),
+        ) {
+            let blake3 = Blake3::new([7; 32]);
+            let verifier = Ed255195Verifier::new(SigningKey::from_bytes(&[7; 32]).verifying_key());
+            proptest::prop_assert!(!blake3.verify(&mut data.as_slice(), &sig).unwrap_or(false));
+            proptest::prop_assert!(!verifier.verify(&mut data.as_slice(), &sig).unwrap_or(false));
+            let _ = verifier.verify_prehashed(&mut data.as_slice(), &sig);
+        }
+
+        #[test]
+        fn prop_parse_signature_never_panics(signature in "\\PC*", tag in "[a-z0-9]{0,10}") {
+            let _ = parse_signature(&signature);
+            let _ = parse_signature(&format!("{}:{}", tag, signature));
+        }
     }
 }
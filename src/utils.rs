@@ -1,5 +1,10 @@
-use std::fs::File;
-use std::io::Read;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use zeroize::Zeroizing;
 
 pub fn get_reader(input: &str) -> anyhow::Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -9,3 +14,211 @@ pub fn get_reader(input: &str) -> anyhow::Result<Box<dyn Read>> {
     };
     Ok(reader)
 }
+
+pub fn get_writer(output: &str) -> anyhow::Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(output)?)
+    };
+    Ok(writer)
+}
+
+/// Like `get_writer`, but a file is only readable by its owner, for secret
+/// keys.
+pub fn get_secret_writer(output: &str) -> anyhow::Result<Box<dyn Write>> {
+    if output == "-" {
+        return get_writer(output);
+    }
+    Ok(Box::new(create_secret_file(output)?))
+}
+
+/// Creates or truncates `path` with mode 0600 on unix.
+pub fn create_secret_file(path: impl AsRef<Path>) -> anyhow::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options.open(path)?;
+    // the mode only applies to new files, an existing one keeps its own
+    #[cfg(unix)]
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// Runs `write` against `output` for plaintext that is only good once it has
+/// all been authenticated. A file is written to an owner-only temporary next
+/// to it and renamed over `output` on success, so a failure leaves neither
+/// partial plaintext nor a truncated `output` behind.
+pub fn write_secret_output<T>(
+    output: &str,
+    write: impl FnOnce(Box<dyn Write>) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    if output == "-" {
+        return write(get_writer(output)?);
+    }
+    let path = Path::new(output);
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{} is not a file name", output))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let result = create_secret_file(&tmp).and_then(|file| write(Box::new(file) as Box<dyn Write>));
+    match result {
+        Ok(value) => match std::fs::rename(&tmp, path) {
+            Ok(()) => Ok(value),
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp);
+                Err(e.into())
+            }
+        },
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// Writes decoded bytes to `output` ("-" for raw stdout). Without an output
+/// they are printed as text if they are UTF-8, and as a hexdump otherwise.
+pub fn write_decoded(output: Option<&str>, decoded: Vec<u8>) -> anyhow::Result<()> {
+    match output {
+        Some(output) => {
+            let mut writer = get_writer(output)?;
+            writer.write_all(&decoded)?;
+            writer.flush()?;
+        }
+        None => match String::from_utf8(decoded) {
+            Ok(decoded) => println!("{}", decoded),
+            Err(e) => {
+                let decoded = e.into_bytes();
+                eprintln!(
+                    "Decoded {} bytes of binary data, use -o to save them",
+                    decoded.len()
+                );
+                print!("{}", hexdump(&decoded, 256));
+            }
+        },
+    }
+    Ok(())
+}
+
+/// `xxd` style dump of the first `limit` bytes, for previewing binary data.
+pub fn hexdump(data: &[u8], limit: usize) -> String {
+    let mut out = String::new();
+    for (i, chunk) in data[..data.len().min(limit)].chunks(16).enumerate() {
+        let _ = write!(out, "{:08x}: ", i * 16);
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(b) => {
+                    let _ = write!(out, "{:02x}", b);
+                }
+                None => out.push_str("  "),
+            }
+            if j % 2 == 1 {
+                out.push(' ');
+            }
+        }
+        out.push(' ');
+        out.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push('\n');
+    }
+    if data.len() > limit {
+        let _ = writeln!(out, "... {} more bytes", data.len() - limit);
+    }
+    out
+}
+
+/// Ends the process with `code` once a command has reported its outcome
+/// itself, `main` prints nothing more for it.
+#[derive(Debug)]
+pub struct Exit(pub u8);
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "exit status {}", self.0)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+/// Reads a secret from the first line of `input`, prompting without echo
+/// when `input` is an interactive stdin.
+pub fn read_secret(input: &str, prompt: &str) -> anyhow::Result<Zeroizing<String>> {
+    if input == "-" && std::io::stdin().is_terminal() {
+        return Ok(Zeroizing::new(rpassword::prompt_password(prompt)?));
+    }
+    let mut line = Zeroizing::new(String::new());
+    BufReader::new(get_reader(input)?).read_line(&mut line)?;
+    let secret = line.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        anyhow::bail!("No secret found in {}", input);
+    }
+    Ok(Zeroizing::new(secret.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        let dump = hexdump(b"rcli\x00\x01 binary data!!", 16);
+        assert_eq!(
+            dump,
+            "00000000: 7263 6c69 0001 2062 696e 6172 7920 6461  rcli.. binary da\n\
+             ... 4 more bytes\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_files_are_owner_only() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("secret.key");
+        create_secret_file(&path)?.write_all(b"new")?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o600
+        );
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+        get_secret_writer(&path.to_string_lossy())?.write_all(b"existing")?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(std::fs::read(&path)?, b"existing");
+        Ok(())
+    }
+
+    #[test]
+    fn test_secret_output_is_replaced_only_on_success() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("plain.txt");
+        let output = path.to_string_lossy();
+        std::fs::write(&path, b"old")?;
+
+        let result: anyhow::Result<()> = write_secret_output(&output, |mut writer| {
+            writer.write_all(b"partial")?;
+            anyhow::bail!("authentication failed")
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&path)?, b"old");
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+
+        write_secret_output(&output, |mut writer| Ok(writer.write_all(b"new")?))?;
+        assert_eq!(std::fs::read(&path)?, b"new");
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::process::{Command, Output};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+fn rcli(args: &[&str]) -> anyhow::Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_rcli"))
+        .args(args)
+        .output()?)
+}
+
+fn sign(format: &str, key: &str) -> anyhow::Result<String> {
+    let output = rcli(&[
+        "text",
+        "sign",
+        "-i",
+        "fixtures/otp.txt",
+        "-k",
+        key,
+        "--format",
+        format,
+    ])?;
+    assert!(output.status.success());
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+fn verify_status(input: &str, key: &str, signature: &str, extra: &[&str]) -> anyhow::Result<i32> {
+    let args = [
+        &[
+            "text",
+            "verify",
+            "-i",
+            input,
+            "-k",
+            key,
+            "--signature",
+            signature,
+        ],
+        extra,
+    ]
+    .concat();
+    let status = rcli(&args)?.status;
+    Ok(status.code().expect("exited normally"))
+}
+
+#[test]
+fn test_verify_exit_codes() -> anyhow::Result<()> {
+    let ed25519 = sign("ed25519", "fixtures/ed25519.sk")?;
+    let blake3 = sign("blake3", "fixtures/blake3.txt")?;
+    let pk = "fixtures/ed25519.pk";
+    let dir = tempfile::tempdir()?;
+    let short_key = dir.path().join("short.key");
+    std::fs::write(&short_key, b"short")?;
+    let short_key = short_key.to_string_lossy();
+    let unreadable = dir.path().to_string_lossy();
+
+    assert_eq!(verify_status("fixtures/otp.txt", pk, &ed25519, &[])?, 0);
+    assert_eq!(verify_status("fixtures/index.html", pk, &ed25519, &[])?, 1);
+    // the tag and --format disagree
+    assert_eq!(
+        verify_status("fixtures/otp.txt", pk, &ed25519, &["--format", "ed25519ph"])?,
+        2
+    );
+    assert_eq!(
+        verify_status("fixtures/otp.txt", pk, "ed25519:AAAA", &[])?,
+        3
+    );
+    // a BLAKE3 MAC can't stand in for an Ed25519 signature
+    assert_eq!(
+        verify_status("fixtures/otp.txt", "fixtures/blake3.txt", &ed25519, &[])?,
+        3
+    );
+    assert_eq!(
+        verify_status(
+            "fixtures/otp.txt",
+            &short_key,
+            &blake3,
+            &["--format", "blake3"]
+        )?,
+        4
+    );
+    // a directory opens but can't be read
+    assert_eq!(verify_status(&unreadable, pk, &ed25519, &[])?, 5);
+    Ok(())
+}
+
+#[test]
+fn test_base64_decode_streams_binary_to_a_pipe() -> anyhow::Result<()> {
+    let data: Vec<u8> = (0..=255).collect();
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("data.b64");
+    std::fs::write(&input, STANDARD.encode(&data))?;
+
+    // the test harness captures stdout, so it is a pipe and not a terminal
+    let output = rcli(&["base64", "decode", "-i", &input.to_string_lossy()])?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, data);
+    assert!(output.stderr.is_empty());
+    Ok(())
+}